@@ -0,0 +1,240 @@
+use crate::hud::LOW_FUEL;
+use crate::{FlightOutcome, D};
+use ggez::audio::{SoundData, SoundSource, Source};
+use ggez::{Context, GameResult};
+use rand::rngs::StdRng;
+use rand::*;
+use std::f32::consts::PI;
+
+static SAMPLE_RATE: u32 = 22050;
+
+struct Sounds {
+    // Looping, louder the more throttle
+    rumble: Source,
+    // Looping while low on fuel
+    alarm: Source,
+    touchdown: Source,
+    explosion: Source,
+}
+
+impl Sounds {
+    fn new(ctx: &mut Context) -> GameResult<Self> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut source =
+            |samples: Vec<D>| Source::from_data(ctx, SoundData::from_bytes(&wav(&samples)));
+        let mut sounds = Sounds {
+            rumble: source(rumble(&mut rng))?,
+            alarm: source(alarm())?,
+            touchdown: source(thump(&mut rng))?,
+            explosion: source(explosion(&mut rng))?,
+        };
+        for looping in &mut [&mut sounds.rumble, &mut sounds.alarm] {
+            looping.set_repeat(true);
+            looping.set_volume(0.);
+            looping.play()?;
+        }
+        Ok(sounds)
+    }
+}
+
+/// Engine rumble, low fuel alarm, touchdown and crash sounds, all synthesized
+/// on start so no sound files are needed.
+///
+/// Without an audio device every call does nothing.
+pub struct Audio {
+    sounds: Option<Sounds>,
+    volume: D,
+    muted: bool,
+    // To play the touchdown sounds once the outcome changes
+    outcome: FlightOutcome,
+}
+
+impl Audio {
+    pub fn disabled() -> Self {
+        Audio {
+            sounds: None,
+            volume: 1.,
+            muted: false,
+            outcome: FlightOutcome::default(),
+        }
+    }
+
+    /// Synthesizes all sounds, staying silent if they cannot be played.
+    pub fn new(ctx: &mut Context) -> Self {
+        let sounds = match Sounds::new(ctx) {
+            Ok(sounds) => Some(sounds),
+            Err(error) => {
+                eprintln!("sound disabled: {}", error);
+                None
+            }
+        };
+        Audio {
+            sounds,
+            ..Self::disabled()
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: D) {
+        self.volume = volume.clamp(0., 1.);
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// Follows the flight, `flying` only while it is on screen and not paused.
+    ///
+    /// The looping sounds only play while `flying`, the touchdown sounds play
+    /// whenever the outcome changes, as the screen may already have moved on.
+    pub fn update(&mut self, flying: bool, throttle: D, fuel: D, outcome: FlightOutcome) {
+        let volume = if self.muted { 0. } else { self.volume };
+        let looping = if flying { volume } else { 0. };
+        let cue = Cue::between(self.outcome, outcome);
+        self.outcome = outcome;
+        let sounds = match self.sounds.as_mut() {
+            Some(sounds) => sounds,
+            None => return,
+        };
+        sounds.rumble.set_volume(looping * throttle);
+        let low_fuel = outcome == FlightOutcome::InFlight && fuel < LOW_FUEL;
+        sounds
+            .alarm
+            .set_volume(if low_fuel { looping * 0.5 } else { 0. });
+        if let Some(cue) = cue.filter(|_| volume > 0.) {
+            let sound = match cue {
+                Cue::Touchdown => &mut sounds.touchdown,
+                Cue::Explosion => &mut sounds.explosion,
+            };
+            sound.set_volume(volume);
+            if let Err(error) = sound.play_detached() {
+                eprintln!("cannot play sound: {}", error);
+            }
+        }
+    }
+}
+
+// Sounds played once rather than looping
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cue {
+    Touchdown,
+    Explosion,
+}
+
+impl Cue {
+    // Sound for the flight ending between two updates, none otherwise
+    fn between(before: FlightOutcome, after: FlightOutcome) -> Option<Cue> {
+        match (before, after) {
+            (FlightOutcome::InFlight, FlightOutcome::Landed) => Some(Cue::Touchdown),
+            (FlightOutcome::InFlight, FlightOutcome::Crashed(_)) => Some(Cue::Explosion),
+            _ => None,
+        }
+    }
+}
+
+// Mono 16 bit PCM WAV file of `samples` between -1 and 1
+fn wav(samples: &[D]) -> Vec<u8> {
+    let data_length = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_length as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_length).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_length.to_le_bytes());
+    for sample in samples {
+        let sample = (sample.clamp(-1., 1.) * i16::MAX as D) as i16;
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+// Seconds since the start of every sample of a sound `seconds` long
+fn times(seconds: D) -> impl Iterator<Item = D> {
+    (0..(seconds * SAMPLE_RATE as D) as usize).map(|sample| sample as D / SAMPLE_RATE as D)
+}
+
+// Brown noise over a low hum, its end blended into its start so it loops without a click
+fn rumble(rng: &mut StdRng) -> Vec<D> {
+    let length = SAMPLE_RATE as usize * 2;
+    let blend = SAMPLE_RATE as usize / 10;
+    let mut level: D = 0.;
+    let noise: Vec<D> = times((length + blend) as D / SAMPLE_RATE as D)
+        .map(|time| {
+            level = (level + rng.gen_range(-1., 1.) * 0.05) * 0.995;
+            level * 4. + (2. * PI * 45. * time).sin() * 0.2
+        })
+        .collect();
+    (0..length)
+        .map(|index| {
+            if index < blend {
+                let share = index as D / blend as D;
+                noise[index] * share + noise[length + index] * (1. - share)
+            } else {
+                noise[index]
+            }
+        })
+        .collect()
+}
+
+// Two short beeps per half second
+fn alarm() -> Vec<D> {
+    times(0.5)
+        .map(|time| {
+            let on = time % 0.25 < 0.1;
+            if on {
+                (2. * PI * 880. * time).sin() * 0.6
+            } else {
+                0.
+            }
+        })
+        .collect()
+}
+
+// Quickly fading low thud with a click on top
+fn thump(rng: &mut StdRng) -> Vec<D> {
+    times(0.4)
+        .map(|time| {
+            (2. * PI * 55. * time).sin() * (-time * 12.).exp()
+                + rng.gen_range(-1., 1.) * 0.3 * (-time * 60.).exp()
+        })
+        .collect()
+}
+
+// Noise that gets darker while it fades
+fn explosion(rng: &mut StdRng) -> Vec<D> {
+    let mut level: D = 0.;
+    times(2.)
+        .map(|time| {
+            let smoothing = 0.3 * (-time * 2.).exp() + 0.02;
+            level += (rng.gen_range(-1., 1.) - level) * smoothing;
+            level * 2. * (-time * 2.5).exp()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CrashReason;
+    use FlightOutcome::*;
+
+    #[test]
+    fn touchdown_sounds_play_once_when_the_flight_ends() {
+        let crashed = Crashed(CrashReason::TooFast);
+        assert_eq!(Cue::between(InFlight, Landed), Some(Cue::Touchdown));
+        assert_eq!(Cue::between(InFlight, crashed), Some(Cue::Explosion));
+        assert_eq!(Cue::between(InFlight, InFlight), None);
+        assert_eq!(Cue::between(Landed, Landed), None);
+        assert_eq!(Cue::between(crashed, crashed), None);
+        // Starting the next flight is silent
+        assert_eq!(Cue::between(Landed, InFlight), None);
+        assert_eq!(Cue::between(crashed, InFlight), None);
+    }
+}
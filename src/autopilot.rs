@@ -0,0 +1,315 @@
+use crate::terrain::{Terrain, Topology};
+use crate::{ControlInput, Orientation, PhysicsProfile, Point, RotationMode, Vector, D};
+use std::f32::consts::FRAC_PI_4;
+use std::time::Duration;
+
+// Distance from the lander's center to its base and to either side of it when upright
+static HALF_HEIGHT: D = 15.;
+static HALF_WIDTH: D = 7.5;
+// Share of full thrust above which the suicide burn has to start
+static BURN_THRESHOLD: D = 0.8;
+// Below this height above the pad the lander is kept upright
+static FLARE_HEIGHT: D = 25.;
+
+/// Lander state the autopilot steers by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LanderState {
+    pub position: Point,
+    pub velocity: Vector,
+    // Radians away from upright, positive when leaning right
+    pub attitude: D,
+    // Acceleration at full throttle with the current mass
+    pub max_acceleration: D,
+    pub fuel: D,
+}
+
+/// Anything that can fly the lander in place of the player.
+pub trait Pilot {
+    fn control(
+        &mut self,
+        state: &LanderState,
+        terrain: &Terrain,
+        profile: &PhysicsProfile,
+        delta: Duration,
+    ) -> ControlInput;
+}
+
+/// Proportional-integral-derivative controller.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pid {
+    pub kp: D,
+    pub ki: D,
+    pub kd: D,
+    // Bound of the integral term against windup
+    pub limit: D,
+    integral: D,
+    previous: Option<D>,
+}
+
+impl Pid {
+    pub const fn new(kp: D, ki: D, kd: D, limit: D) -> Self {
+        Pid {
+            kp,
+            ki,
+            kd,
+            limit,
+            integral: 0.,
+            previous: None,
+        }
+    }
+
+    pub fn update(&mut self, error: D, seconds: D) -> D {
+        self.integral = (self.integral + error * seconds).clamp(-self.limit, self.limit);
+        let derivative = match self.previous {
+            Some(previous) if seconds > 0. => (error - previous) / seconds,
+            _ => 0.,
+        };
+        self.previous = Some(error);
+        self.kp * error + self.ki * self.integral + self.kd * derivative
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = 0.;
+        self.previous = None;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    // Flying over to the pad above the terrain in between
+    Approach,
+    // Falling onto the pad, `burning` once the suicide burn has started
+    Descent { burning: bool },
+}
+
+/// Flies the lander onto a landing pad, producing the same inputs a player would.
+///
+/// It first kills the horizontal drift and moves over the pad while keeping
+/// clear of the terrain in between, then drops onto the pad and brakes with a
+/// late, fuel saving suicide burn. Without a pad it hovers where it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Autopilot {
+    // Index into the terrain's pads, none to hover
+    pub pad: Option<usize>,
+    // Turns the horizontal speed error into a horizontal acceleration
+    drift: Pid,
+    phase: Phase,
+    // Height kept above the ground on the way to the pad
+    pub clearance: D,
+    // Speed the suicide burn brakes down to
+    pub touchdown_speed: D,
+    // Steepest lean while approaching the pad
+    pub max_tilt: D,
+    // Horizontal speed approached per unit of distance to the pad
+    pub approach_gain: D,
+    pub max_approach_speed: D,
+}
+
+impl Autopilot {
+    pub fn new(pad: usize) -> Self {
+        Autopilot {
+            pad: Some(pad),
+            drift: Pid::new(1.2, 0.1, 0.2, 20.),
+            phase: Phase::Approach,
+            clearance: 60.,
+            touchdown_speed: 5.,
+            max_tilt: 0.4,
+            approach_gain: 0.5,
+            max_approach_speed: 40.,
+        }
+    }
+
+    /// Targets the pad horizontally closest to `x`, if there are any pads.
+    pub fn nearest_pad(terrain: &Terrain, x: D) -> Option<Self> {
+        (0..terrain.pads().len())
+            .min_by(|&a, &b| {
+                let distance = |pad| offset(terrain, x, pad_center(terrain, pad)).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .map(Self::new)
+    }
+
+    /// Holds the lander above the ground where it is, for terrains without pads.
+    pub fn hover() -> Self {
+        Autopilot {
+            pad: None,
+            ..Self::new(0)
+        }
+    }
+}
+
+impl Pilot for Autopilot {
+    fn control(
+        &mut self,
+        state: &LanderState,
+        terrain: &Terrain,
+        profile: &PhysicsProfile,
+        delta: Duration,
+    ) -> ControlInput {
+        let seconds = delta.as_secs_f32();
+        // Hovering never gets close enough to a pad to descend
+        let (dx, margin, height) = match self.pad.and_then(|pad| terrain.pads().get(pad)) {
+            Some(pad) => {
+                let bounds = terrain.pad_bounds(pad);
+                (
+                    offset(terrain, state.position.x, (bounds.start + bounds.end) / 2.),
+                    (bounds.end - bounds.start) / 2. - HALF_WIDTH,
+                    terrain.point(pad.start).y - (state.position.y + HALF_HEIGHT),
+                )
+            }
+            None => (0., 0., D::INFINITY),
+        };
+        let velocity = state.velocity;
+
+        self.phase = match self.phase {
+            Phase::Approach if dx.abs() < margin / 2. && velocity.x.abs() < 0.5 => {
+                Phase::Descent { burning: false }
+            }
+            // Drifted away high enough to go around again
+            Phase::Descent { .. } if dx.abs() > margin && height > 2. * FLARE_HEIGHT => {
+                self.drift.reset();
+                Phase::Approach
+            }
+            phase => phase,
+        };
+
+        // Fast enough to get there, slow enough to still stop over the pad leaning
+        // half as far as allowed
+        let braking = profile.gravity * self.max_tilt.tan() / 2.;
+        let target_speed = dx.signum()
+            * (dx.abs() * self.approach_gain)
+                .min((2. * braking * dx.abs()).sqrt())
+                .min(self.max_approach_speed);
+        let acceleration = self.drift.update(target_speed - velocity.x, seconds);
+        let max_tilt = match self.phase {
+            _ if height < FLARE_HEIGHT => 0.,
+            Phase::Approach => self.max_tilt,
+            Phase::Descent { burning: false } => 0.,
+            Phase::Descent { burning: true } => Orientation::step_angle(),
+        };
+        let tilt = (acceleration / profile.gravity.max(1.))
+            .atan()
+            .clamp(-max_tilt, max_tilt);
+
+        // Share of full thrust that cancels gravity plus `lift`, upwards
+        let lean = state.attitude.cos().max(0.1);
+        let throttle_for =
+            |lift: D| (profile.gravity + lift) / (state.max_acceleration * lean).max(D::EPSILON);
+        let mut throttle = match self.phase {
+            Phase::Approach => {
+                // Ground on the way to the pad, under the hull and where the drift
+                // carries it within the next seconds
+                let ahead = velocity.x * 2.;
+                let from = dx.min(ahead).min(0.) - 2. * HALF_WIDTH;
+                let to = dx.max(ahead).max(0.) + 2. * HALF_WIDTH;
+                let ground = highest_ground(terrain, state.position.x + from, to - from);
+                let ceiling = ground - self.clearance - HALF_HEIGHT;
+                let target_sink = ((ceiling - state.position.y) * 0.5).clamp(-30., 30.);
+                throttle_for((velocity.y - target_sink) * 2.)
+            }
+            Phase::Descent { burning } => {
+                let excess = velocity.y.powi(2) - self.touchdown_speed.powi(2);
+                let braking = if velocity.y <= self.touchdown_speed {
+                    0.
+                } else if height > 1. {
+                    excess / (2. * height)
+                } else {
+                    D::INFINITY
+                };
+                let throttle = throttle_for(braking);
+                let burning = burning || throttle > BURN_THRESHOLD;
+                self.phase = Phase::Descent { burning };
+                if burning {
+                    throttle
+                } else {
+                    0.
+                }
+            }
+        };
+        let error = tilt - state.attitude;
+        // Thrusting far off the intended direction does more harm than good
+        if error.abs() > FRAC_PI_4 || state.fuel <= 0. {
+            throttle = 0.;
+        }
+        let rotate = match profile.rotation {
+            RotationMode::Stepped if error.abs() > Orientation::step_angle() / 2. => error.signum(),
+            RotationMode::Stepped => 0.,
+            RotationMode::Continuous => (error * 4.).clamp(-1., 1.),
+        };
+        ControlInput {
+            rotate,
+            throttle: throttle.clamp(0., 1.),
+            abort: false,
+        }
+    }
+}
+
+pub fn pad_center(terrain: &Terrain, pad: usize) -> D {
+    let bounds = terrain.pad_bounds(&terrain.pads()[pad]);
+    (bounds.start + bounds.end) / 2.
+}
+
+// Horizontal distance from `from` to `to`, the short way around in a wrapping world
+pub fn offset(terrain: &Terrain, from: D, to: D) -> D {
+    let dx = to - from;
+    match terrain.topology() {
+        Topology::Wrapping => {
+            let width = terrain.width();
+            (dx + width / 2.).rem_euclid(width) - width / 2.
+        }
+        Topology::Bounded => dx,
+    }
+}
+
+// Topmost ground between `x` and `dx` further, as a screen coordinate
+fn highest_ground(terrain: &Terrain, x: D, dx: D) -> D {
+    let step = terrain.width() / terrain.segments().max(1) as D;
+    let samples = (dx.abs() / step).ceil() as usize + 1;
+    (0..=samples)
+        .map(|sample| x + dx * sample as D / samples as D)
+        .filter_map(|x| terrain.surface_at(x))
+        .fold(D::INFINITY, D::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Position;
+    use crate::sim::{self, Controller};
+    use crate::{FlightOutcome, Moonar};
+
+    #[test]
+    fn lands_seeded_levels_on_each_preset() {
+        let profiles: Vec<_> = ["moon", "mars", "earth"]
+            .iter()
+            .map(|&name| PhysicsProfile::preset(name).unwrap())
+            .collect();
+        // One seed per level keeps the debug build quick
+        for level in 1..=3 {
+            let seed = u64::from(level);
+            let report = sim::run(&Controller::Autopilot, seed..seed + 1, &profiles, level);
+            for run in &report.runs {
+                assert_eq!(
+                    run.outcome, "landed",
+                    "{} seed {} level {}: {:?}",
+                    run.profile, run.seed, level, run.crash_reason
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hovers_without_a_pad() {
+        let mut flight = Moonar::with_seed(0, PhysicsProfile::moon());
+        flight.pilot = Some(Box::new(Autopilot::hover()));
+        while flight.elapsed < Duration::from_secs(20) {
+            let frame = flight.next_frame(ControlInput::default).unwrap();
+            flight.step(frame.input, frame.delta);
+        }
+        assert_eq!(flight.outcome, FlightOutcome::InFlight);
+        let position = flight.player_data::<Position>().current;
+        let ground = flight.terrain.surface_at(position.x).unwrap();
+        assert!(ground - position.y > HALF_HEIGHT, "{:?}", position);
+        assert!(flight.fuel() > 0.);
+    }
+}
@@ -0,0 +1,142 @@
+use moonar_lander::env::LanderEnv;
+use moonar_lander::error::Result;
+use moonar_lander::neural::Network;
+use moonar_lander::profile::PhysicsProfile;
+use moonar_lander::{FlightOutcome, D};
+use rand::rngs::StdRng;
+use rand::*;
+use std::path::PathBuf;
+
+// Ticks every network decision is held for, to speed up training
+static FRAME_SKIP: u32 = 4;
+// Best networks carried over unchanged into the next generation
+static ELITES: usize = 2;
+static TOURNAMENT_SIZE: usize = 3;
+// Chance of a weight to be changed, and by how much at most
+static MUTATION_RATE: f64 = 0.1;
+static MUTATION_SIZE: D = 0.3;
+
+struct Candidate {
+    network: Network,
+    // Mean reward per flight
+    fitness: D,
+    landings: usize,
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+fn arg_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    arg_value(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+// Total reward of `network` over the terrains of `seeds`
+fn evaluate(network: Network, env: &mut LanderEnv, seeds: &[u64]) -> Candidate {
+    let mut total = 0.;
+    let mut landings = 0;
+    for &seed in seeds {
+        let mut observation = env.reset(seed);
+        loop {
+            let (next, reward, done, info) = env.step(network.act(&observation));
+            total += reward;
+            observation = next;
+            if done {
+                if info.outcome == FlightOutcome::Landed {
+                    landings += 1;
+                }
+                break;
+            }
+        }
+    }
+    Candidate {
+        network,
+        fitness: total / seeds.len().max(1) as D,
+        landings,
+    }
+}
+
+fn tournament<'a>(population: &'a [Candidate], rng: &mut impl Rng) -> &'a Network {
+    let winner = (0..TOURNAMENT_SIZE)
+        .map(|_| &population[rng.gen_range(0, population.len())])
+        .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+        .expect("Tournament without contestants");
+    &winner.network
+}
+
+// Uniform crossover of two parents, followed by mutation
+fn offspring(mother: &Network, father: &Network, rng: &mut impl Rng) -> Network {
+    let weights = mother
+        .weights
+        .iter()
+        .zip(&father.weights)
+        .map(|(&a, &b)| {
+            let weight = if rng.gen() { a } else { b };
+            if rng.gen_bool(MUTATION_RATE) {
+                weight + rng.gen_range(-MUTATION_SIZE, MUTATION_SIZE)
+            } else {
+                weight
+            }
+        })
+        .collect();
+    Network {
+        layers: mother.layers.clone(),
+        weights,
+    }
+}
+
+fn main() -> Result {
+    let generations = arg_or("--generations", 100u32);
+    let size = arg_or("--population", 50usize).max(ELITES + 1);
+    let terrains = arg_or("--terrains", 8usize);
+    let hidden: Vec<usize> = arg_value("--hidden")
+        .unwrap_or_else(|| "8".to_owned())
+        .split(',')
+        .filter_map(|size| size.trim().parse().ok())
+        .collect();
+    let out = PathBuf::from(arg_value("--out").unwrap_or_else(|| "pilot.toml".to_owned()));
+    let profile = arg_value("--profile")
+        .map(|arg| PhysicsProfile::from_arg(&arg))
+        .transpose()?
+        .unwrap_or_default();
+    let mut rng = StdRng::seed_from_u64(arg_or("--seed", rand::random()));
+
+    let mut env = LanderEnv::new(profile);
+    env.level = arg_or("--level", 1u32);
+    env.frame_skip = FRAME_SKIP;
+    env.max_steps /= FRAME_SKIP as usize;
+    let mut networks: Vec<Network> = (0..size)
+        .map(|_| Network::random(&hidden, &mut rng))
+        .collect();
+    for generation in 1..=generations {
+        // Fresh terrains every generation so the pilots cannot learn them by heart
+        let seeds: Vec<u64> = (0..terrains).map(|_| rng.gen()).collect();
+        let mut population: Vec<Candidate> = networks
+            .into_iter()
+            .map(|network| evaluate(network, &mut env, &seeds))
+            .collect();
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        let mean = population.iter().map(|c| c.fitness).sum::<D>() / population.len() as D;
+        let best = &population[0];
+        println!(
+            "generation {:>4}  best {:>9.2}  mean {:>9.2}  landed {}/{}",
+            generation, best.fitness, mean, best.landings, terrains
+        );
+        best.network.save(&out)?;
+
+        networks = population
+            .iter()
+            .take(ELITES)
+            .map(|candidate| candidate.network.clone())
+            .collect();
+        while networks.len() < size {
+            let mother = tournament(&population, &mut rng);
+            let father = tournament(&population, &mut rng);
+            networks.push(offspring(mother, father, &mut rng));
+        }
+    }
+    println!("saved the best pilot to {}", out.display());
+    Ok(())
+}
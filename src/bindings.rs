@@ -0,0 +1,253 @@
+use crate::D;
+use ggez::event::KeyCode;
+use ggez::{Context, GameError, GameResult};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Action {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Abort,
+    // Hands the controls to the autopilot and takes them back
+    Autopilot,
+    Mute,
+    Pause,
+    // Moves on from the title and result screens
+    Continue,
+    // Opens the key bindings from the title screen and leaves them again
+    Rebind,
+    Quit,
+}
+
+impl Action {
+    pub fn all() -> [Action; 10] {
+        use Action::*;
+        [
+            RotateLeft,
+            RotateRight,
+            Thrust,
+            Abort,
+            Autopilot,
+            Mute,
+            Pause,
+            Continue,
+            Rebind,
+            Quit,
+        ]
+    }
+
+    // Name in the bindings file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::RotateLeft => "rotate_left",
+            Action::RotateRight => "rotate_right",
+            Action::Thrust => "thrust",
+            Action::Abort => "abort",
+            Action::Autopilot => "autopilot",
+            Action::Mute => "mute",
+            Action::Pause => "pause",
+            Action::Continue => "continue",
+            Action::Rebind => "rebind",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Action> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::RotateLeft => "rotate left",
+            Action::RotateRight => "rotate right",
+            Action::Thrust => "thrust",
+            Action::Abort => "abort",
+            Action::Autopilot => "autopilot",
+            Action::Mute => "mute",
+            Action::Pause => "pause",
+            Action::Continue => "continue",
+            Action::Rebind => "change keys",
+            Action::Quit => "quit",
+        }
+    }
+}
+
+// Keys that can be named in a bindings file
+#[rustfmt::skip]
+static KEYS: &[KeyCode] = {
+    use KeyCode::*;
+    &[
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        Left, Right, Up, Down, Space, Return, Escape, Back, Tab,
+        Insert, Delete, Home, End, PageUp, PageDown,
+        LShift, RShift, LControl, RControl, LAlt, RAlt,
+        Comma, Period, Slash, Semicolon, Apostrophe, LBracket, RBracket, Backslash, Minus, Equals,
+        Grave, Add, Subtract, Multiply, Divide, NumpadEnter, Pause,
+    ]
+};
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    KEYS.iter().copied().find(|&key| key_name(key) == name)
+}
+
+/// Whether `key` can be saved in a bindings file.
+pub fn is_bindable(key: KeyCode) -> bool {
+    KEYS.contains(&key)
+}
+
+/// Which keys trigger which action, any number of keys per action.
+///
+/// On disk the bindings are a TOML table from action to key names, e.g.
+/// `thrust = ["Space", "Up"]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        let keys = vec![
+            (Action::RotateLeft, vec![Left]),
+            (Action::RotateRight, vec![Right]),
+            (Action::Thrust, vec![Space, Up]),
+            (Action::Abort, vec![Down]),
+            (Action::Autopilot, vec![F]),
+            (Action::Mute, vec![M]),
+            (Action::Pause, vec![P]),
+            (Action::Continue, vec![Space, Return]),
+            (Action::Rebind, vec![Tab]),
+            (Action::Quit, vec![Escape]),
+        ];
+        Bindings {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    pub fn triggers(&self, key: KeyCode, action: Action) -> bool {
+        self.keys(action).contains(&key)
+    }
+
+    /// Whether any key of `action` is held down.
+    pub fn is_pressed(&self, ctx: &Context, action: Action) -> bool {
+        use ggez::input::keyboard::is_key_pressed;
+        self.keys(action)
+            .iter()
+            .any(|&key| is_key_pressed(ctx, key))
+    }
+
+    /// 1 while any key of `action` is held down, 0 otherwise.
+    pub fn axis(&self, ctx: &Context, action: Action) -> D {
+        if self.is_pressed(ctx, action) {
+            1.
+        } else {
+            0.
+        }
+    }
+
+    /// Adds `key` to `action`, false if it is not a key bindings can be saved with.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> bool {
+        if !is_bindable(key) {
+            return false;
+        }
+        let keys = self.keys.entry(action).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        true
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys.insert(action, Vec::new());
+    }
+
+    /// Reads the bindings at `path`, falling back to the defaults if there is no such file.
+    pub fn load(path: &Path) -> GameResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let invalid =
+            |message: String| GameError::ConfigError(format!("invalid bindings: {}", message));
+        let names: BTreeMap<String, Vec<String>> = toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|error| invalid(error.to_string()))?;
+        let mut bindings = Self::default();
+        for (action, names) in names {
+            let action = Action::parse(&action)
+                .ok_or_else(|| invalid(format!("unknown action {:?}", action)))?;
+            let keys = names
+                .iter()
+                .map(|name| {
+                    parse_key(name).ok_or_else(|| invalid(format!("unknown key {:?}", name)))
+                })
+                .collect::<GameResult<_>>()?;
+            bindings.keys.insert(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> GameResult {
+        let names: BTreeMap<&str, Vec<String>> = self
+            .keys
+            .iter()
+            .map(|(&action, keys)| (action.name(), keys.iter().copied().map(key_name).collect()))
+            .collect();
+        let source = toml::to_string(&names)
+            .map_err(|error| GameError::ConfigError(format!("cannot save bindings: {}", error)))?;
+        fs::write(path, source)?;
+        GameResult::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bindable_key_parses_back() {
+        for &key in KEYS {
+            assert_eq!(parse_key(&key_name(key)), Some(key));
+        }
+    }
+
+    #[test]
+    fn default_keys_are_all_bindable() {
+        let bindings = Bindings::default();
+        for &action in &Action::all() {
+            assert!(!bindings.keys(action).is_empty(), "{:?}", action);
+            assert!(bindings.keys(action).iter().all(|&key| is_bindable(key)));
+        }
+    }
+
+    #[test]
+    fn saved_bindings_load_the_same() {
+        let mut bindings = Bindings::default();
+        bindings.clear(Action::Abort);
+        assert!(bindings.bind(Action::Thrust, KeyCode::W));
+        assert!(!bindings.bind(Action::Thrust, KeyCode::Sleep));
+        let path = crate::scratch_path("bindings.toml");
+        bindings.save(&path).unwrap();
+        let loaded = Bindings::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), bindings);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{Point, Vector, D};
+use ggez::graphics::DrawParam;
+use std::time::Duration;
+
+/// Maps world coordinates to the screen, following the lander and zooming in
+/// when it gets close to the ground.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    // World point shown in the middle of the screen
+    pub position: Point,
+    pub zoom: D,
+    // Screen size in pixels
+    pub viewport: (D, D),
+    // World size the view is kept inside of
+    pub bounds: (D, D),
+    // Whether the world repeats horizontally every `bounds.0`
+    pub wrapping: bool,
+    // Altitude below which the camera switches to the close-up
+    pub close_up_altitude: D,
+    pub close_up_zoom: D,
+    // Fraction of the remaining distance covered per second
+    pub follow_rate: D,
+}
+
+impl Camera {
+    pub fn new(viewport: (D, D), bounds: (D, D), wrapping: bool) -> Self {
+        Camera {
+            position: Point::new(bounds.0 / 2., bounds.1 / 2.),
+            zoom: 1.,
+            viewport,
+            bounds,
+            wrapping,
+            close_up_altitude: 120.,
+            close_up_zoom: 2.5,
+            follow_rate: 4.,
+        }
+    }
+
+    /// Eases towards `target`, zoomed in if it is less than `close_up_altitude` above ground.
+    pub fn follow(&mut self, target: Point, altitude: Option<D>, delta: Duration) {
+        let blend = (delta.as_secs_f32() * self.follow_rate).min(1.);
+        let zoom = match altitude {
+            Some(altitude) if altitude < self.close_up_altitude => self.close_up_zoom,
+            _ => 1.,
+        };
+        self.zoom += (zoom - self.zoom) * blend;
+        let target = self.clamp(self.nearest_copy(target));
+        self.position += (target - self.position) * blend;
+        self.position = self.clamp(self.position);
+        if self.wrapping {
+            self.position.x = self.position.x.rem_euclid(self.bounds.0);
+        }
+    }
+
+    /// Copy of `point` closest to the view in a wrapping world.
+    pub fn nearest_copy(&self, point: Point) -> Point {
+        if !self.wrapping {
+            return point;
+        }
+        let width = self.bounds.0;
+        let laps = ((self.position.x - point.x) / width).round();
+        Point::new(point.x + laps * width, point.y)
+    }
+
+    /// Horizontal offsets at which a wrapping world has to be drawn to fill the view.
+    pub fn copies(&self) -> Vec<D> {
+        if self.wrapping {
+            vec![-self.bounds.0, 0., self.bounds.0]
+        } else {
+            vec![0.]
+        }
+    }
+
+    // Keeps the visible area inside the world bounds where possible
+    fn clamp(&self, center: Point) -> Point {
+        let half_width = self.viewport.0 / self.zoom / 2.;
+        let half_height = self.viewport.1 / self.zoom / 2.;
+        let axis = |value: D, half: D, bound: D| {
+            if 2. * half >= bound {
+                bound / 2.
+            } else {
+                value.max(half).min(bound - half)
+            }
+        };
+        let x = if self.wrapping {
+            center.x
+        } else {
+            axis(center.x, half_width, self.bounds.0)
+        };
+        Point::new(x, axis(center.y, half_height, self.bounds.1))
+    }
+
+    pub fn project(&self, point: Point) -> Point {
+        let center = Vector::new(self.viewport.0 / 2., self.viewport.1 / 2.);
+        Point::origin() + (point - self.position) * self.zoom + center
+    }
+
+    /// Turns parameters for drawing in world coordinates into screen parameters.
+    pub fn transform(&self, params: DrawParam) -> DrawParam {
+        let dest = Point::new(params.dest.x, params.dest.y);
+        params
+            .dest(self.project(dest))
+            .scale(Vector::new(params.scale.x, params.scale.y) * self.zoom)
+    }
+}
@@ -0,0 +1,373 @@
+#[cfg(feature = "graphics")]
+use crate::white;
+use crate::{Force, PhysicsProfile, Point, RotationMode, Vector, D};
+#[cfg(feature = "graphics")]
+use ggez::graphics::Color;
+use legion::prelude::*;
+use nalgebra as na;
+use rand::Rng;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// World position, keeping the previous tick around for render interpolation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub current: Point,
+    pub previous: Point,
+}
+
+impl Position {
+    pub fn new(at: Point) -> Self {
+        Position {
+            current: at,
+            previous: at,
+        }
+    }
+
+    /// Point `alpha` of the way from the previous to the current tick.
+    #[cfg(feature = "graphics")]
+    pub fn interpolate(&self, alpha: D) -> Point {
+        self.previous + (self.current - self.previous) * alpha
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity(pub Vector);
+
+/// Heading either quantized into `dir_count` steps turned with a cooldown, or
+/// turned smoothly at the rate of one full turn per `dir_count` steps.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    pub mode: RotationMode,
+    pub dir: u8,
+    // Radians, only used in continuous mode
+    pub heading: D,
+    pub turn_cooldown: Duration,
+    // Time per step
+    pub turn_time: Duration,
+    // -1 turns left at full rate, 1 turns right
+    pub turning: D,
+}
+
+impl Orientation {
+    pub const fn dir_count() -> u8 {
+        32
+    }
+
+    /// Angle between two neighbouring directions.
+    pub fn step_angle() -> D {
+        2. * PI / Self::dir_count() as D
+    }
+
+    fn dir(&mut self, d: u8) {
+        self.dir = d % Self::dir_count();
+    }
+
+    pub fn change_dir(&mut self, d: i8, delta: Duration) {
+        if self.turn_cooldown <= delta {
+            self.turn_cooldown = self.turn_time;
+            self.dir(((self.dir as i8) + d) as u8)
+        }
+    }
+
+    /// Turns by `turning` for `delta`, according to the rotation mode.
+    pub fn turn(&mut self, delta: Duration) {
+        match self.mode {
+            RotationMode::Stepped => {
+                if self.turning != 0. {
+                    self.change_dir(self.turning.signum() as i8, delta);
+                }
+                self.turn_cooldown = self
+                    .turn_cooldown
+                    .checked_sub(delta)
+                    .unwrap_or(Duration::from_micros(0));
+            }
+            RotationMode::Continuous => {
+                let seconds = delta.as_secs_f32();
+                self.heading = (self.heading + self.turning * self.angular_rate() * seconds)
+                    .rem_euclid(2. * PI);
+            }
+        }
+    }
+
+    // Radians per second in continuous mode
+    fn angular_rate(&self) -> D {
+        Self::step_angle() / self.turn_time.as_secs_f32()
+    }
+
+    pub fn angle(&self) -> D {
+        match self.mode {
+            RotationMode::Stepped => Self::step_angle() * self.dir as D,
+            RotationMode::Continuous => self.heading,
+        }
+    }
+
+    // Angular distance between the nose and `up`
+    pub fn tilt(&self, up: Vector) -> D {
+        let heading: Vector = na::Rotation2::new(self.angle()) * Vector::x();
+        heading.angle(&up)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thruster {
+    // Force when pointing to the right
+    pub force: Force,
+    // Fuel mass burned per second of thrust
+    pub burn_rate: D,
+    // Fraction of full force, 0 when off
+    pub throttle: D,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FuelTank {
+    pub dry_mass: D,
+    pub fuel: D,
+    pub capacity: D,
+}
+
+impl FuelTank {
+    pub fn full(dry_mass: D, capacity: D) -> Self {
+        FuelTank {
+            dry_mass,
+            fuel: capacity,
+            capacity,
+        }
+    }
+
+    pub fn mass(&self) -> D {
+        self.dry_mass + self.fuel
+    }
+
+    pub fn burn(&mut self, amount: D) {
+        self.fuel = (self.fuel - amount).max(0.);
+    }
+}
+
+/// Outline in local coordinates that collides with the terrain, shared per archetype.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub hull: Vec<Point>,
+}
+
+impl Collider {
+    pub fn world_hull(&self, position: Point, angle: D) -> Vec<Point> {
+        let rotation = na::Rotation2::new(angle);
+        self.hull
+            .iter()
+            .map(|point| rotation * point + position.coords)
+            .collect()
+    }
+}
+
+/// Stroked outline in local coordinates, shared per archetype.
+#[cfg(feature = "graphics")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Renderable {
+    pub outline: Vec<Point>,
+    pub color: Color,
+}
+
+fn lander_hull() -> Vec<Point> {
+    let (width, height) = (15., 30.);
+    vec![
+        Point::new(height / 2., 0.),
+        Point::new(-height / 2., width / 2.),
+        Point::new(-height / 2., -width / 2.),
+    ]
+}
+
+/// Middle of the lander's base in local coordinates, where the exhaust leaves.
+#[cfg(feature = "graphics")]
+pub fn nozzle() -> Point {
+    Point::new(-15., 0.)
+}
+
+pub fn spawn_lander(world: &mut World, at: Point, profile: &PhysicsProfile) -> Entity {
+    let collider = Collider {
+        hull: lander_hull(),
+    };
+    #[cfg(feature = "graphics")]
+    let shared = (
+        collider,
+        Renderable {
+            outline: lander_hull(),
+            color: white(),
+        },
+    );
+    // Nothing to draw it with
+    #[cfg(not(feature = "graphics"))]
+    let shared = (collider,);
+    let components = (
+        Position::new(at),
+        Velocity(Vector::zeros()),
+        Orientation {
+            mode: profile.rotation,
+            turn_time: profile.turn_time(),
+            ..Orientation::default()
+        },
+        Thruster {
+            force: profile.thruster(),
+            burn_rate: profile.burn_rate,
+            throttle: 0.,
+        },
+        FuelTank::full(profile.dry_mass, profile.fuel_capacity),
+    );
+    world.insert_from(shared, vec![components])[0]
+}
+
+/// Tags the pieces of a wrecked lander.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wreck;
+
+/// Piece of a wrecked hull, a line segment tumbling around its middle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment {
+    // From the middle of the segment to one of its ends, before turning
+    pub half: Vector,
+    // Radians turned so far, and per second
+    pub angle: D,
+    pub spin: D,
+    // Seconds since the crash
+    pub age: D,
+    // Lying still on the ground
+    pub resting: bool,
+}
+
+impl Fragment {
+    #[cfg_attr(not(feature = "graphics"), allow(dead_code))]
+    pub fn ends(&self, middle: Point) -> (Point, Point) {
+        let half = na::Rotation2::new(self.angle) * self.half;
+        (middle - half, middle + half)
+    }
+}
+
+/// Breaks `hull`, in world coordinates, into two fragments per edge that keep
+/// `velocity` and fly apart from the middle of the hull.
+pub fn spawn_debris(
+    world: &mut World,
+    hull: &[Point],
+    velocity: Vector,
+    rng: &mut impl Rng,
+) -> Vec<Entity> {
+    let (speed, spin) = (60., 2. * PI);
+    let center = hull
+        .iter()
+        .fold(Vector::zeros(), |sum, point| sum + point.coords)
+        / hull.len() as D;
+    let components: Vec<_> = hull
+        .iter()
+        .zip(hull.iter().cycle().skip(1))
+        .flat_map(|(&a, &b)| {
+            let middle = a + (b - a) / 2.;
+            vec![(a, middle), (middle, b)]
+        })
+        .map(|(a, b)| {
+            let middle = a + (b - a) / 2.;
+            let outwards = (middle.coords - center)
+                .try_normalize(D::EPSILON)
+                .unwrap_or_else(|| -Vector::y());
+            // Biased upwards so the pieces do not just slide along the ground
+            let impulse = (outwards * rng.gen_range(0.5, 1.) - Vector::y() * 0.5) * speed;
+            (
+                Position::new(middle),
+                Velocity(velocity + impulse),
+                Fragment {
+                    half: (b - a) / 2.,
+                    angle: 0.,
+                    spin: rng.gen_range(-spin, spin),
+                    age: 0.,
+                    resting: false,
+                },
+            )
+        })
+        .collect();
+    world.insert_from((Wreck,), components).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stepped() -> Orientation {
+        Orientation {
+            turn_time: Duration::from_millis(100),
+            ..Orientation::default()
+        }
+    }
+
+    #[test]
+    fn full_set_of_steps_is_a_full_turn() {
+        assert_eq!(
+            Orientation::step_angle() * Orientation::dir_count() as D,
+            2. * PI
+        );
+    }
+
+    #[test]
+    fn stepping_all_directions_comes_back_around() {
+        let mut orientation = stepped();
+        for step in 1..=Orientation::dir_count() {
+            orientation.change_dir(1, orientation.turn_time);
+            assert_eq!(orientation.dir, step % Orientation::dir_count());
+        }
+        assert_eq!(orientation.angle(), 0.);
+    }
+
+    #[test]
+    fn quarter_of_the_steps_is_a_right_angle() {
+        let mut orientation = stepped();
+        orientation.dir(Orientation::dir_count() / 4);
+        assert_eq!(orientation.angle(), PI / 2.);
+    }
+
+    #[test]
+    fn continuous_rotation_follows_the_angular_rate() {
+        let mut orientation = Orientation {
+            mode: RotationMode::Continuous,
+            turning: 1.,
+            ..stepped()
+        };
+        // A full turn takes `dir_count` step times
+        let quarter = orientation.turn_time * (Orientation::dir_count() / 4) as u32;
+        orientation.turn(quarter);
+        assert!((orientation.angle() - PI / 2.).abs() < 1e-5);
+        orientation.turning = -1.;
+        orientation.turn(quarter * 2);
+        assert!((orientation.angle() - 3. * PI / 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn hull_breaks_into_two_fragments_per_edge() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut world = Universe::new(None).create_world();
+        let hull = Collider {
+            hull: lander_hull(),
+        }
+        .world_hull(Point::new(100., 100.), 0.3);
+        let fragments = spawn_debris(
+            &mut world,
+            &hull,
+            Vector::new(10., 20.),
+            &mut StdRng::seed_from_u64(1),
+        );
+        assert_eq!(fragments.len(), 2 * hull.len());
+        // Each pair of fragments spans one edge of the hull, half of it each
+        for (pair, (&a, &b)) in fragments
+            .chunks(2)
+            .zip(hull.iter().zip(hull.iter().cycle().skip(1)))
+        {
+            let length = |entity| {
+                world
+                    .entity_data::<Fragment>(entity)
+                    .map(|fragment| 2. * fragment.half.norm())
+                    .unwrap()
+            };
+            let edge = (b - a).norm();
+            assert!((length(pair[0]) + length(pair[1]) - edge).abs() < 1e-3);
+            assert!(world.entity_data::<Velocity>(pair[0]).is_some());
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::autopilot::{self, Autopilot, LanderState};
+use crate::terrain::Terrain;
+use crate::{ControlInput, FlightOutcome, Moonar, PhysicsProfile, Point, Vector, D, TICK};
+use std::time::Duration;
+
+// Height above the ground is sampled at this many points around the lander
+pub static TERRAIN_SAMPLES: usize = 9;
+// Horizontal distance between two terrain samples
+static SAMPLE_SPACING: D = 25.;
+
+/// Controls for one step, the same a player has.
+pub type Action = ControlInput;
+
+/// What an agent sees of the flight.
+///
+/// As a vector (see `to_vec`) these are, in order: position x and y, velocity
+/// x and y, attitude, fuel, pad offset and then the `TERRAIN_SAMPLES` terrain
+/// samples from left to right. World coordinates have y pointing down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub position: Point,
+    pub velocity: Vector,
+    // Radians away from upright, positive when leaning right
+    pub attitude: D,
+    // Share of a full tank left
+    pub fuel: D,
+    // Horizontal distance to the center of the nearest pad, 0 without pads
+    pub pad_offset: D,
+    // Height above the ground, centered below the lander, 0 off the map
+    pub terrain: Vec<D>,
+}
+
+impl Observation {
+    pub fn size() -> usize {
+        7 + TERRAIN_SAMPLES
+    }
+
+    pub fn to_vec(&self) -> Vec<D> {
+        let mut values = vec![
+            self.position.x,
+            self.position.y,
+            self.velocity.x,
+            self.velocity.y,
+            self.attitude,
+            self.fuel,
+            self.pad_offset,
+        ];
+        values.extend_from_slice(&self.terrain);
+        values
+    }
+}
+
+/// Weights of the terms the reward of a step is made of.
+///
+/// Besides the outcome of a flight, the reward rewards getting closer to a
+/// pad, slower and more upright as the difference of a potential. A finished
+/// flight has a potential of 0, so the shaping does not change which policy is
+/// best, and the speed at touchdown counts towards the outcome instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RewardShaping {
+    // Once for a landing, whatever the pad
+    pub landed: D,
+    // Per point of the landing score
+    pub score: D,
+    // Taken once for a crash
+    pub crashed: D,
+    // Taken once per unit of speed at touchdown, landed or crashed
+    pub touchdown: D,
+    // Taken per full tank burned
+    pub fuel: D,
+    // Taken every step
+    pub step: D,
+    // Potential per unit of horizontal distance to the nearest pad
+    pub distance: D,
+    // Potential per unit of speed
+    pub speed: D,
+    // Potential per radian away from upright
+    pub tilt: D,
+}
+
+impl Default for RewardShaping {
+    fn default() -> Self {
+        RewardShaping {
+            landed: 100.,
+            score: 0.1,
+            crashed: 100.,
+            touchdown: 1.,
+            fuel: 10.,
+            step: 0.,
+            distance: 0.05,
+            speed: 0.5,
+            tilt: 10.,
+        }
+    }
+}
+
+/// Extra details of a step that are not part of the observation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Info {
+    pub outcome: FlightOutcome,
+    pub score: u16,
+    // Simulated time since the reset
+    pub elapsed: Duration,
+    // Cut off after `max_steps` rather than ended by a touchdown
+    pub truncated: bool,
+}
+
+/// Gym-style environment that flies one flight per episode without a window.
+pub struct LanderEnv {
+    flight: Moonar,
+    pub profile: PhysicsProfile,
+    // Terrain generator and parameters of the flights
+    pub level: u32,
+    pub shaping: RewardShaping,
+    pub max_steps: usize,
+    // Ticks simulated per step, all with the same action
+    pub frame_skip: u32,
+    steps: usize,
+}
+
+impl LanderEnv {
+    pub fn new(profile: PhysicsProfile) -> Self {
+        LanderEnv {
+            flight: Moonar::with_seed(0, profile.clone()),
+            profile,
+            level: 1,
+            shaping: RewardShaping::default(),
+            // A minute of flight
+            max_steps: 60 * 120,
+            frame_skip: 1,
+            steps: 0,
+        }
+    }
+
+    /// Starts a new flight over the terrain of `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.flight = Moonar::with_level(seed, self.level, self.profile.clone());
+        self.steps = 0;
+        self.observation()
+    }
+
+    /// Applies `action` for `frame_skip` ticks, returning what the agent sees
+    /// afterwards, the reward, whether the episode is over and details of the step.
+    pub fn step(&mut self, action: Action) -> (Observation, D, bool, Info) {
+        let before = self.observation();
+        let score = self.flight.score;
+        let mut reward = 0.;
+        if self.flight.outcome == FlightOutcome::InFlight {
+            for _ in 0..self.frame_skip.max(1) {
+                self.flight.step(action, TICK);
+            }
+            self.steps += 1;
+            let after = self.observation();
+            let potential = match self.flight.outcome {
+                FlightOutcome::InFlight => self.potential(&after),
+                _ => 0.,
+            };
+            reward = potential
+                - self.potential(&before)
+                - self.shaping.fuel * (before.fuel - after.fuel)
+                - self.shaping.step;
+            if let Some(velocity) = self.flight.touchdown {
+                reward -= self.shaping.touchdown * velocity.norm();
+            }
+            reward += match self.flight.outcome {
+                FlightOutcome::InFlight => 0.,
+                FlightOutcome::Landed => {
+                    self.shaping.landed
+                        + self.shaping.score * self.flight.score.saturating_sub(score) as D
+                }
+                FlightOutcome::Crashed(_) => -self.shaping.crashed,
+            };
+        }
+        let truncated =
+            self.flight.outcome == FlightOutcome::InFlight && self.steps >= self.max_steps;
+        let info = Info {
+            outcome: self.flight.outcome,
+            score: self.flight.score,
+            elapsed: self.flight.elapsed,
+            truncated,
+        };
+        let done = truncated || self.flight.outcome != FlightOutcome::InFlight;
+        (self.observation(), reward, done, info)
+    }
+
+    pub fn observation(&self) -> Observation {
+        observe(
+            &self.flight.lander_state(),
+            &self.flight.terrain,
+            &self.flight.profile,
+        )
+    }
+
+    // Higher the closer the lander is to a calm, upright hover over a pad
+    fn potential(&self, observation: &Observation) -> D {
+        -(self.shaping.distance * observation.pad_offset.abs()
+            + self.shaping.speed * observation.velocity.norm()
+            + self.shaping.tilt * observation.attitude.abs())
+    }
+}
+
+/// What an agent in `state` sees of the flight over `terrain`.
+pub(crate) fn observe(
+    state: &LanderState,
+    terrain: &Terrain,
+    profile: &PhysicsProfile,
+) -> Observation {
+    let pad_offset = Autopilot::nearest_pad(terrain, state.position.x)
+        .and_then(|pilot| pilot.pad)
+        .map_or(0., |pad| {
+            autopilot::offset(
+                terrain,
+                state.position.x,
+                autopilot::pad_center(terrain, pad),
+            )
+        });
+    let first = -((TERRAIN_SAMPLES / 2) as D) * SAMPLE_SPACING;
+    let samples = (0..TERRAIN_SAMPLES)
+        .map(|sample| {
+            let x = state.position.x + first + sample as D * SAMPLE_SPACING;
+            terrain
+                .surface_at(x)
+                .map_or(0., |surface| surface - state.position.y)
+        })
+        .collect();
+    Observation {
+        position: state.position,
+        velocity: state.velocity,
+        attitude: state.attitude,
+        fuel: state.fuel / profile.fuel_capacity,
+        pad_offset,
+        terrain: samples,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_flies_the_same() {
+        let mut env = LanderEnv::new(PhysicsProfile::default());
+        let action = Action {
+            rotate: 1.,
+            throttle: 0.5,
+            abort: false,
+        };
+        let first = env.reset(7);
+        let step = env.step(action);
+        assert_eq!(env.reset(7), first);
+        assert_eq!(env.step(action), step);
+    }
+
+    #[test]
+    fn fast_crash_is_punished() {
+        let mut env = LanderEnv::new(PhysicsProfile::moon());
+        env.reset(3);
+        let mut last = None;
+        while last.is_none() {
+            let (_, reward, done, info) = env.step(Action::default());
+            if done {
+                last = Some((reward, info));
+            }
+        }
+        let (reward, info) = last.unwrap();
+        assert!(matches!(info.outcome, FlightOutcome::Crashed(_)));
+        assert!(env.flight.touchdown.unwrap().norm() > 40.);
+        // Stopping on the ground must not pay back the speed the lander crashed with
+        assert!(reward < -env.shaping.crashed, "{}", reward);
+    }
+
+    #[test]
+    fn shaping_adds_up_to_the_starting_potential() {
+        let mut env = LanderEnv::new(PhysicsProfile::moon());
+        let start = env.reset(3);
+        let mut total = 0.;
+        loop {
+            let (_, reward, done, _) = env.step(Action::default());
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        let speed = env.flight.touchdown.unwrap().norm();
+        let expected = -env.potential(&start) - env.shaping.crashed - env.shaping.touchdown * speed;
+        assert!((total - expected).abs() < 1e-2, "{} != {}", total, expected);
+    }
+
+    #[test]
+    fn observation_vector_has_the_documented_length() {
+        let mut env = LanderEnv::new(PhysicsProfile::default());
+        assert_eq!(env.reset(1).to_vec().len(), Observation::size());
+    }
+}
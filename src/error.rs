@@ -0,0 +1,39 @@
+use std::fmt;
+use std::io;
+
+/// What can go wrong loading or saving profiles, networks and the like.
+#[derive(Debug)]
+pub enum Error {
+    // A value that does not make sense, with why
+    Config(String),
+    Io(io::Error),
+}
+
+pub type Result<T = ()> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "{}", message),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<Error> for ggez::GameError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Config(message) => ggez::GameError::ConfigError(message),
+            Error::Io(error) => ggez::GameError::from(error),
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use crate::audio::Audio;
+use crate::bindings::{self, Action, Bindings};
+use crate::neural::Network;
+use crate::{
+    white, CrashReason, FlightOutcome, Gamepad, Moonar, PhysicsProfile, Point, D, TICKS_PER_SECOND,
+};
+use ggez::event::{self, Axis, Button, EventHandler, GamepadId, KeyCode, KeyMods};
+use ggez::graphics::{self, Color, DrawParam, Drawable, Text};
+use ggez::{timer, Context, GameResult};
+use rand::rngs::StdRng;
+use rand::*;
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+static HIGH_SCORE_SLOTS: usize = 5;
+static MAX_NAME_LENGTH: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u16,
+}
+
+/// Screens of the arcade loop.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameState {
+    Title,
+    Flying,
+    // Touchdown result
+    Landed,
+    Crashed(CrashReason),
+    // Fuel ran out
+    GameOver,
+    // Name typed in so far
+    HighScoreEntry(String),
+    Paused,
+    // Key rebinding screen, `waiting` for the key to add to the selected action
+    Bindings { selected: usize, waiting: bool },
+}
+
+/// Runs flight after flight, carrying score and fuel over until the tank is empty.
+pub struct Game {
+    state: GameState,
+    flight: Moonar,
+    // Source of the seeds of all following flights
+    seeds: StdRng,
+    high_scores: Vec<HighScore>,
+    gamepad: Gamepad,
+    bindings: Bindings,
+    // Where rebound keys are saved
+    bindings_path: Option<PathBuf>,
+    // Flown by the autopilot behind the title screen
+    demo: Option<Moonar>,
+    // Whether the autopilot flies every flight
+    autopilot: bool,
+    // Flies every flight instead of the autopilot or the player
+    network: Option<Network>,
+    audio: Audio,
+}
+
+impl Game {
+    pub fn new(seed: u64, profile: PhysicsProfile) -> Self {
+        Game {
+            state: GameState::Title,
+            // Flown on the terrain of `seed` itself so the seed on the HUD reproduces it
+            flight: Moonar::with_seed(seed, profile),
+            seeds: StdRng::seed_from_u64(seed),
+            high_scores: Vec::new(),
+            gamepad: Gamepad::default(),
+            bindings: Bindings::default(),
+            bindings_path: None,
+            demo: None,
+            autopilot: false,
+            network: None,
+            audio: Audio::disabled(),
+        }
+    }
+
+    /// Skips the title screen and starts right away with `flight`.
+    pub fn flying(flight: Moonar) -> Self {
+        Game {
+            state: GameState::Flying,
+            seeds: StdRng::seed_from_u64(flight.seed),
+            flight,
+            high_scores: Vec::new(),
+            gamepad: Gamepad::default(),
+            bindings: Bindings::default(),
+            bindings_path: None,
+            demo: None,
+            autopilot: false,
+            network: None,
+            audio: Audio::disabled(),
+        }
+    }
+
+    pub fn record_to(&mut self, path: Option<PathBuf>) {
+        self.flight.record_to = path;
+    }
+
+    pub fn dead_zone(&mut self, dead_zone: D) {
+        self.gamepad = Gamepad::new(dead_zone);
+    }
+
+    pub fn bindings(&mut self, bindings: Bindings, path: PathBuf) {
+        self.bindings = bindings;
+        self.bindings_path = Some(path);
+    }
+
+    pub fn audio(&mut self, audio: Audio) {
+        self.audio = audio;
+    }
+
+    /// Lets the autopilot fly every flight from now on.
+    pub fn autopilot(&mut self) {
+        self.autopilot = true;
+        self.engage_pilot();
+    }
+
+    /// Lets a trained network fly every flight from now on.
+    pub fn network(&mut self, network: Network) {
+        self.network = Some(network);
+        self.engage_pilot();
+    }
+
+    fn engage_pilot(&mut self) {
+        if let Some(network) = &self.network {
+            self.flight.pilot = Some(Box::new(network.clone()));
+        } else if self.autopilot {
+            self.flight.engage_autopilot();
+        }
+    }
+
+    // A new demo flight on random terrain, its seed is not taken from `seeds`
+    // so the flights after the title stay the same
+    fn start_demo(&mut self) {
+        let mut demo = Moonar::with_seed(rand::random(), self.flight.profile.clone());
+        demo.engage_autopilot();
+        self.demo = Some(demo);
+    }
+
+    fn next_flight(&mut self, level: u32) {
+        let profile = self.flight.profile.clone();
+        let mut flight = Moonar::with_level(self.seeds.gen(), level, profile);
+        flight.refuel(self.flight.fuel());
+        flight.score = self.flight.score;
+        flight.record_to = self.flight.record_to.take();
+        self.flight = flight;
+        self.engage_pilot();
+        self.state = GameState::Flying;
+    }
+
+    fn restart(&mut self) {
+        let record_to = self.flight.record_to.take();
+        let profile = self.flight.profile.clone();
+        self.flight = Moonar::with_seed(self.seeds.gen(), profile);
+        self.flight.record_to = record_to;
+        self.engage_pilot();
+        self.state = GameState::Title;
+    }
+
+    fn qualifies(&self, score: u16) -> bool {
+        score > 0
+            && (self.high_scores.len() < HIGH_SCORE_SLOTS
+                || self.high_scores.iter().any(|entry| entry.score < score))
+    }
+
+    fn enter_high_score(&mut self, name: String) {
+        self.high_scores.push(HighScore {
+            name,
+            score: self.flight.score,
+        });
+        self.high_scores.sort_by_key(|entry| Reverse(entry.score));
+        self.high_scores.truncate(HIGH_SCORE_SLOTS);
+    }
+
+    // The continue action moves on from every screen but the flight itself
+    fn advance(&mut self) {
+        let out_of_fuel = self.flight.fuel() <= 0.;
+        match self.state.clone() {
+            GameState::Title => {
+                self.demo = None;
+                self.state = GameState::Flying;
+            }
+            GameState::Landed | GameState::Crashed(_) if out_of_fuel => {
+                self.state = GameState::GameOver
+            }
+            GameState::Landed => self.next_flight(self.flight.level + 1),
+            GameState::Crashed(_) => self.next_flight(self.flight.level),
+            GameState::GameOver if self.qualifies(self.flight.score) => {
+                self.state = GameState::HighScoreEntry(String::new())
+            }
+            GameState::GameOver => self.restart(),
+            GameState::HighScoreEntry(name) => {
+                self.enter_high_score(name);
+                self.restart();
+            }
+            GameState::Paused => self.state = GameState::Flying,
+            GameState::Flying | GameState::Bindings { .. } => {}
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        match self.state {
+            GameState::Flying => self.state = GameState::Paused,
+            GameState::Paused => self.state = GameState::Flying,
+            _ => {}
+        }
+    }
+
+    // Arrows pick an action, Enter adds the next key pressed, Backspace clears
+    // and the rebind action or Escape saves and goes back to the title
+    fn rebind(&mut self, key: KeyCode, selected: usize, waiting: bool) {
+        let actions = Action::all();
+        let action = actions[selected];
+        self.state = match key {
+            // Keys that cannot be saved keep waiting for another one
+            _ if waiting => GameState::Bindings {
+                selected,
+                waiting: !self.bindings.bind(action, key),
+            },
+            KeyCode::Up => GameState::Bindings {
+                selected: (selected + actions.len() - 1) % actions.len(),
+                waiting,
+            },
+            KeyCode::Down => GameState::Bindings {
+                selected: (selected + 1) % actions.len(),
+                waiting,
+            },
+            KeyCode::Return => GameState::Bindings {
+                selected,
+                waiting: true,
+            },
+            KeyCode::Back | KeyCode::Delete => {
+                self.bindings.clear(action);
+                GameState::Bindings { selected, waiting }
+            }
+            _ if key == KeyCode::Escape || self.bindings.triggers(key, Action::Rebind) => {
+                if let Some(path) = &self.bindings_path {
+                    if let Err(error) = self.bindings.save(path) {
+                        eprintln!("{}", error);
+                    }
+                }
+                GameState::Title
+            }
+            _ => GameState::Bindings { selected, waiting },
+        };
+    }
+
+    fn key_names(&self, action: Action) -> String {
+        let names: Vec<_> = self
+            .bindings
+            .keys(action)
+            .iter()
+            .map(|&key| bindings::key_name(key).to_lowercase())
+            .collect();
+        if names.is_empty() {
+            "---".to_owned()
+        } else {
+            names.join(", ")
+        }
+    }
+
+    fn prompt(&self, action: Action, what: &str) -> String {
+        let key = self.bindings.keys(action).first().copied();
+        match key {
+            Some(key) => format!(
+                "press {} to {}",
+                bindings::key_name(key).to_lowercase(),
+                what
+            ),
+            None => format!("bind a key to {}", what),
+        }
+    }
+
+    fn banner(&self) -> Vec<String> {
+        match &self.state {
+            GameState::Title => {
+                let mut lines = vec![
+                    "MOONAR LANDER".to_owned(),
+                    self.prompt(Action::Continue, "start"),
+                    self.prompt(Action::Rebind, "change keys"),
+                ];
+                if !self.high_scores.is_empty() {
+                    lines.push(String::new());
+                    lines.extend(
+                        self.high_scores
+                            .iter()
+                            .map(|entry| format!("{:<10} {:>6}", entry.name, entry.score)),
+                    );
+                }
+                lines
+            }
+            GameState::Flying => Vec::new(),
+            GameState::Landed => vec![
+                "THE EAGLE HAS LANDED".to_owned(),
+                format!("score {}", self.flight.score),
+                self.prompt(Action::Continue, "continue"),
+            ],
+            GameState::Crashed(reason) => vec![
+                "CRASHED".to_owned(),
+                match reason {
+                    CrashReason::TooFast => "you came in too fast",
+                    CrashReason::Tilted => "you touched down at an angle",
+                    CrashReason::UnevenGround => "the ground was not level",
+                }
+                .to_owned(),
+                self.prompt(Action::Continue, "continue"),
+            ],
+            GameState::GameOver => vec![
+                "GAME OVER".to_owned(),
+                format!("final score {}", self.flight.score),
+            ],
+            GameState::HighScoreEntry(name) => vec![
+                "NEW HIGH SCORE".to_owned(),
+                format!("enter your name: {}_", name),
+            ],
+            GameState::Paused => vec!["PAUSED".to_owned(), self.prompt(Action::Pause, "resume")],
+            GameState::Bindings { selected, waiting } => {
+                let mut lines = vec!["KEYS".to_owned(), String::new()];
+                lines.extend(Action::all().iter().enumerate().map(|(index, &action)| {
+                    let marker = if index == *selected { '>' } else { ' ' };
+                    format!(
+                        "{} {:<12} {}",
+                        marker,
+                        action.label(),
+                        self.key_names(action)
+                    )
+                }));
+                lines.push(String::new());
+                lines.push(if *waiting {
+                    "press the key to add".to_owned()
+                } else {
+                    "enter adds a key, backspace clears, escape returns".to_owned()
+                });
+                lines
+            }
+        }
+    }
+
+    fn draw_banner(&self, ctx: &mut Context) -> GameResult {
+        let (width, height) = Moonar::screen_size();
+        let line_height = 24.;
+        let lines = self.banner();
+        let top = height / 3. - lines.len() as D * line_height / 2.;
+        for (index, line) in lines.into_iter().enumerate() {
+            let text = Text::new(line);
+            let (text_width, _) = text.dimensions(ctx);
+            let dest = Point::new(
+                (width - text_width as D) / 2.,
+                top + index as D * line_height,
+            );
+            text.draw(ctx, DrawParam::default().dest(dest).color(white()))?;
+        }
+        GameResult::Ok(())
+    }
+
+    // Runs the demo behind the title screen or the flight on screen
+    fn tick(&mut self, ctx: &mut Context) -> GameResult {
+        if self.state == GameState::Title {
+            if !matches!(&self.demo, Some(demo) if !demo.finished()) {
+                self.start_demo();
+            }
+            if let Some(demo) = self.demo.as_mut() {
+                return demo.update(ctx, &self.bindings, &mut self.gamepad);
+            }
+        }
+        if self.state != GameState::Flying {
+            // Drop the time spent on other screens so the flight does not catch up on it
+            while timer::check_update_time(ctx, TICKS_PER_SECOND) {}
+            return GameResult::Ok(());
+        }
+        self.flight.update(ctx, &self.bindings, &mut self.gamepad)?;
+        if !self.flight.finished() {
+            return GameResult::Ok(());
+        }
+        match self.flight.outcome {
+            FlightOutcome::InFlight => {}
+            FlightOutcome::Landed => self.state = GameState::Landed,
+            FlightOutcome::Crashed(reason) => self.state = GameState::Crashed(reason),
+        }
+        GameResult::Ok(())
+    }
+}
+
+impl EventHandler for Game {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.tick(ctx)?;
+        // After the flight, so a touchdown is heard in the frame it happens
+        self.audio.update(
+            self.state == GameState::Flying,
+            self.flight.throttle(),
+            self.flight.fuel() / self.flight.profile.fuel_capacity,
+            self.flight.outcome,
+        );
+        GameResult::Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, Color::from_rgb(0, 0, 0));
+        match self.demo.as_mut() {
+            Some(demo) if self.state == GameState::Title => demo.draw(ctx)?,
+            _ => self.flight.draw(ctx)?,
+        }
+        self.draw_banner(ctx)?;
+        graphics::present(ctx)
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _: KeyMods, repeat: bool) {
+        match &mut self.state {
+            GameState::Bindings { selected, waiting } => {
+                let (selected, waiting) = (*selected, *waiting);
+                if !repeat {
+                    self.rebind(keycode, selected, waiting);
+                }
+                return;
+            }
+            // Typed letters must not trigger the actions bound to them
+            GameState::HighScoreEntry(name) => {
+                match keycode {
+                    KeyCode::Back => {
+                        name.pop();
+                    }
+                    KeyCode::Return if !repeat => self.advance(),
+                    KeyCode::Escape => event::quit(ctx),
+                    _ => {}
+                }
+                return;
+            }
+            _ => {}
+        }
+        if repeat {
+            return;
+        }
+        if self.bindings.triggers(keycode, Action::Quit) {
+            event::quit(ctx);
+        } else if self.bindings.triggers(keycode, Action::Rebind) && self.state == GameState::Title
+        {
+            self.state = GameState::Bindings {
+                selected: 0,
+                waiting: false,
+            };
+        } else if self.bindings.triggers(keycode, Action::Autopilot)
+            && self.state == GameState::Flying
+        {
+            self.flight.toggle_autopilot();
+        } else if self.bindings.triggers(keycode, Action::Mute) {
+            self.audio.toggle_mute();
+        } else if self.bindings.triggers(keycode, Action::Pause) {
+            self.toggle_pause();
+        } else if self.bindings.triggers(keycode, Action::Continue) {
+            self.advance();
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, _: &mut Context, button: Button, id: GamepadId) {
+        self.gamepad.activate(id);
+        match button {
+            Button::Start if self.state == GameState::Flying => self.toggle_pause(),
+            Button::South | Button::Start => self.advance(),
+            _ => {}
+        }
+    }
+
+    fn gamepad_axis_event(&mut self, _: &mut Context, _: Axis, _: f32, id: GamepadId) {
+        self.gamepad.activate(id);
+    }
+
+    fn text_input_event(&mut self, _: &mut Context, character: char) {
+        if let GameState::HighScoreEntry(name) = &mut self.state {
+            if character.is_alphanumeric() && name.chars().count() < MAX_NAME_LENGTH {
+                name.push(character);
+            }
+        }
+    }
+}
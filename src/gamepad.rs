@@ -0,0 +1,68 @@
+use crate::{ControlInput, D};
+use ggez::event::{Axis, Button, GamepadId};
+use ggez::input::gamepad;
+use ggez::Context;
+
+// Sticks rarely rest exactly at zero
+static DEFAULT_DEAD_ZONE: D = 0.2;
+
+/// Reads the lander controls from whichever gamepad was used last.
+///
+/// Pads are picked up by their first event and dropped once they disconnect,
+/// so controllers can be plugged in and out while the game runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gamepad {
+    active: Option<GamepadId>,
+    // Stick and trigger travel that is ignored, as a fraction of full travel
+    pub dead_zone: D,
+}
+
+impl Default for Gamepad {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEAD_ZONE)
+    }
+}
+
+impl Gamepad {
+    pub fn new(dead_zone: D) -> Self {
+        Gamepad {
+            active: None,
+            dead_zone: dead_zone.clamp(0., 0.99),
+        }
+    }
+
+    /// Makes `id` the pad that is read from.
+    pub fn activate(&mut self, id: GamepadId) {
+        self.active = Some(id);
+    }
+
+    // Rescales the travel outside of the dead zone to the full range
+    fn filter(&self, value: D) -> D {
+        if value.abs() <= self.dead_zone {
+            0.
+        } else {
+            value.signum() * (value.abs() - self.dead_zone) / (1. - self.dead_zone)
+        }
+    }
+
+    /// Left stick rotates, the right trigger is the throttle and East aborts.
+    pub fn read(&mut self, ctx: &Context) -> ControlInput {
+        let id = match self.active {
+            Some(id) => id,
+            None => return ControlInput::default(),
+        };
+        let pad = gamepad::gamepad(ctx, id);
+        if !pad.is_connected() {
+            self.active = None;
+            return ControlInput::default();
+        }
+        let trigger = pad
+            .button_data(Button::RightTrigger2)
+            .map_or(0., |data| data.value());
+        ControlInput {
+            rotate: self.filter(pad.value(Axis::LeftStickX)),
+            throttle: self.filter(trigger),
+            abort: pad.is_pressed(Button::East),
+        }
+    }
+}
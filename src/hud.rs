@@ -0,0 +1,118 @@
+use crate::{white, Point, Vector, D};
+use ggez::graphics::{Color, DrawParam, Drawable, Text, TextFragment};
+use ggez::{Context, GameResult};
+use std::time::Duration;
+
+// Share of a full tank below which the fuel gauge and the alarm warn
+pub static LOW_FUEL: D = 0.2;
+static LINE_HEIGHT: D = 18.;
+
+fn warning() -> Color {
+    Color::from_rgb(255, 80, 80)
+}
+
+/// Flight readouts shown in the top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Telemetry {
+    pub level: u32,
+    pub seed: u64,
+    pub score: u16,
+    pub elapsed: Duration,
+    // Fraction of a full tank
+    pub fuel: D,
+    // Height above the ground right below the lander
+    pub altitude: Option<D>,
+    pub velocity: Vector,
+    // Radians away from upright, positive when leaning right
+    pub attitude: D,
+    // Touchdown tolerances of the current profile
+    pub max_speed: D,
+    pub max_tilt: D,
+}
+
+impl Telemetry {
+    // Text of every line and whether it is outside of safe landing limits
+    fn lines(&self) -> Vec<(String, bool)> {
+        let seconds = self.elapsed.as_secs();
+        let (horizontal, vertical) = (self.velocity.x, self.velocity.y);
+        // Touchdown is judged on the whole speed, so both parts warn together
+        let too_fast = self.velocity.norm() > self.max_speed;
+        vec![
+            (format!("score     {}", self.score), false),
+            (
+                format!("time      {}:{:02}", seconds / 60, seconds % 60),
+                false,
+            ),
+            (
+                format!("fuel      {:.0}%", self.fuel * 100.),
+                self.fuel < LOW_FUEL,
+            ),
+            (
+                match self.altitude {
+                    Some(altitude) => format!("altitude  {:.0}", altitude),
+                    None => "altitude  ---".to_owned(),
+                },
+                false,
+            ),
+            (
+                format!(
+                    "h. speed  {:.1} {}",
+                    horizontal.abs(),
+                    if horizontal < 0. { '←' } else { '→' }
+                ),
+                too_fast,
+            ),
+            (
+                format!(
+                    "v. speed  {:.1} {}",
+                    vertical.abs(),
+                    if vertical < 0. { '↑' } else { '↓' }
+                ),
+                too_fast,
+            ),
+            (
+                format!("attitude  {:.0}°", self.attitude.to_degrees()),
+                self.attitude.abs() > self.max_tilt,
+            ),
+            (format!("level {}  seed {}", self.level, self.seed), false),
+        ]
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        for (index, (line, warn)) in self.lines().into_iter().enumerate() {
+            let color = if warn { warning() } else { white() };
+            let dest = Point::new(10., 10. + index as D * LINE_HEIGHT);
+            Text::new(TextFragment::new(line).color(color))
+                .draw(ctx, DrawParam::default().dest(dest))?;
+        }
+        GameResult::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_warns_on_the_whole_velocity() {
+        let telemetry = |velocity| Telemetry {
+            level: 1,
+            seed: 0,
+            score: 0,
+            elapsed: Duration::default(),
+            fuel: 1.,
+            altitude: None,
+            velocity,
+            attitude: 0.,
+            max_speed: 15.,
+            max_tilt: 0.2,
+        };
+        let warnings = |velocity| {
+            let lines = telemetry(velocity).lines();
+            (lines[4].1, lines[5].1)
+        };
+        assert_eq!(warnings(Vector::new(10., 10.)), (false, false));
+        assert_eq!(warnings(Vector::new(10., 12.)), (true, true));
+        assert_eq!(warnings(Vector::new(0., -16.)), (true, true));
+    }
+}
@@ -0,0 +1,722 @@
+#[cfg(feature = "graphics")]
+use ggez::{graphics::*, timer, Context, GameResult};
+use legion::prelude::*;
+use nalgebra as na;
+use rand::rngs::StdRng;
+use rand::*;
+use std::collections::VecDeque;
+#[cfg(feature = "graphics")]
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(feature = "graphics")]
+pub mod audio;
+mod autopilot;
+#[cfg(feature = "graphics")]
+pub mod bindings;
+#[cfg(feature = "graphics")]
+mod camera;
+mod components;
+pub mod env;
+pub mod error;
+#[cfg(feature = "graphics")]
+pub mod game;
+#[cfg(feature = "graphics")]
+mod gamepad;
+#[cfg(feature = "graphics")]
+mod hud;
+pub mod neural;
+#[cfg(feature = "graphics")]
+mod particles;
+pub mod profile;
+pub mod replay;
+pub mod sim;
+mod systems;
+mod terrain;
+
+use autopilot::{Autopilot, LanderState, Pilot};
+#[cfg(feature = "graphics")]
+use bindings::{Action, Bindings};
+#[cfg(feature = "graphics")]
+use camera::Camera;
+use components::{Collider, FuelTank, Orientation, Position, Thruster, Velocity};
+#[cfg(feature = "graphics")]
+use gamepad::Gamepad;
+#[cfg(feature = "graphics")]
+use hud::Telemetry;
+#[cfg(feature = "graphics")]
+use particles::{ParticleSystem, Plume};
+use profile::{PhysicsProfile, RotationMode};
+use replay::{Frame, Replay};
+use terrain::Terrain;
+#[cfg(feature = "graphics")]
+use terrain::Topology;
+
+pub type D = f32;
+pub type Vector = na::Vector2<D>;
+pub type Point = na::Point2<D>;
+
+static LANDING_SCORE: u16 = 50;
+// Physics runs at a fixed rate independent of the frame rate
+static TICKS_PER_SECOND: u32 = 120;
+static TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND as u64);
+
+#[cfg(feature = "graphics")]
+fn white() -> Color {
+    Color::from_rgb(255, 255, 255)
+}
+
+#[cfg(feature = "graphics")]
+fn stroke() -> DrawMode {
+    DrawMode::Stroke(StrokeOptions::default())
+}
+
+fn cross(a: Vector, b: Vector) -> D {
+    a.x * b.y - a.y * b.x
+}
+
+fn segments_intersect(p: (Point, Point), q: (Point, Point)) -> bool {
+    let r = p.1 - p.0;
+    let s = q.1 - q.0;
+    let denominator = cross(r, s);
+    if denominator == 0. {
+        return false;
+    }
+    let t = cross(q.0 - p.0, s) / denominator;
+    let u = cross(q.0 - p.0, r) / denominator;
+    (0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)
+}
+
+// Path of a scratch file of its own for every test
+#[cfg(test)]
+fn scratch_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("moonar-{}-{}", std::process::id(), name))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrashReason {
+    TooFast,
+    Tilted,
+    UnevenGround,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FlightOutcome {
+    #[default]
+    InFlight,
+    Landed,
+    Crashed(CrashReason),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Force(pub f32, pub f32);
+
+impl From<Force> for Vector {
+    fn from(force: Force) -> Self {
+        Vector::new(force.0, force.1)
+    }
+}
+
+impl Force {
+    pub fn to_velocity(self, d: Duration) -> Vector {
+        let scale = d.as_secs_f32();
+        let acceleration: Vector = self.into();
+        acceleration.scale(scale)
+    }
+
+    pub fn per_second(self) -> Vector {
+        self.to_velocity(Duration::from_secs(1))
+    }
+
+    pub fn acceleration(self, mass: D) -> Force {
+        Force(self.0 / mass, self.1 / mass)
+    }
+}
+
+/// Player intent for a single simulation step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ControlInput {
+    // -1 turns left at full rate, 1 turns right
+    pub rotate: D,
+    // Fraction of full thrust
+    pub throttle: D,
+    // Rights the lander and climbs at full thrust, overriding the other controls
+    pub abort: bool,
+}
+
+impl ControlInput {
+    #[cfg(feature = "graphics")]
+    fn from_keyboard(ctx: &Context, bindings: &Bindings) -> Self {
+        ControlInput {
+            rotate: bindings.axis(ctx, Action::RotateRight)
+                - bindings.axis(ctx, Action::RotateLeft),
+            throttle: bindings.axis(ctx, Action::Thrust),
+            abort: bindings.is_pressed(ctx, Action::Abort),
+        }
+    }
+
+    /// Combines two input devices, the stronger control wins on every axis.
+    #[cfg(feature = "graphics")]
+    fn merge(self, other: Self) -> Self {
+        ControlInput {
+            rotate: if other.rotate.abs() > self.rotate.abs() {
+                other.rotate
+            } else {
+                self.rotate
+            },
+            throttle: self.throttle.max(other.throttle),
+            abort: self.abort || other.abort,
+        }
+    }
+}
+
+/// A single flight over one terrain.
+pub struct Moonar {
+    world: World,
+    // The lander driven by `ControlInput`
+    player: Entity,
+    terrain: Terrain,
+    #[cfg(feature = "graphics")]
+    camera: Camera,
+    seed: u64,
+    #[cfg(feature = "graphics")]
+    level: u32,
+    profile: PhysicsProfile,
+    score: u16,
+    // Time spent in the air
+    elapsed: Duration,
+    outcome: FlightOutcome,
+    recording: Replay,
+    // Where to save `recording` once the flight is over
+    #[cfg(feature = "graphics")]
+    record_to: Option<PathBuf>,
+    // Frames still to be played back instead of reading the keyboard
+    playback: Option<VecDeque<Frame>>,
+    // Flies instead of the player when engaged
+    pilot: Option<Box<dyn Pilot>>,
+    // Velocity the lander hit the ground with
+    touchdown: Option<Vector>,
+    #[cfg(feature = "graphics")]
+    particles: ParticleSystem,
+}
+
+impl Default for Moonar {
+    fn default() -> Self {
+        Self::with_seed(rand::random(), PhysicsProfile::default())
+    }
+}
+
+impl Moonar {
+    /// Builds a world whose terrain is fully determined by `seed`.
+    pub fn with_seed(seed: u64, profile: PhysicsProfile) -> Self {
+        Self::with_level(seed, 1, profile)
+    }
+
+    fn with_level(seed: u64, level: u32, profile: PhysicsProfile) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (generator, params) = terrain::for_level(level);
+        let params = terrain::TerrainParams {
+            length: Self::map_length(),
+            ..params
+        };
+        let (width, height) = Self::world_size();
+        let mut terrain = Terrain::new(
+            generator.generate(&params, &mut rng),
+            width / (Self::map_length() as f32),
+            height * 1.1,
+            params.topology,
+        );
+        terrain.carve_pads(Self::pad_count(), &mut rng);
+        let mut world = Universe::new(None).create_world();
+        let player = components::spawn_lander(&mut world, Point::new(100., 100.), &profile);
+        Moonar {
+            world,
+            player,
+            terrain,
+            #[cfg(feature = "graphics")]
+            camera: Camera::new(
+                Self::screen_size(),
+                Self::world_size(),
+                params.topology == Topology::Wrapping,
+            ),
+            seed,
+            #[cfg(feature = "graphics")]
+            level,
+            score: 0,
+            elapsed: Duration::from_secs(0),
+            outcome: FlightOutcome::default(),
+            recording: Replay::new(seed, level, profile.fuel_capacity, &profile),
+            profile,
+            #[cfg(feature = "graphics")]
+            record_to: None,
+            playback: None,
+            pilot: None,
+            touchdown: None,
+            #[cfg(feature = "graphics")]
+            particles: ParticleSystem::new(seed),
+        }
+    }
+
+    /// Plays `replay` back with `profile`, also telling whether the profile is still
+    /// the one it was recorded with; a changed one may play back differently.
+    pub fn from_replay(replay: Replay, profile: PhysicsProfile) -> (Self, bool) {
+        let matches = replay.matches(&profile);
+        let mut game = Self::with_level(replay.seed, replay.level, profile);
+        game.refuel(replay.fuel);
+        game.playback = Some(replay.frames.into_iter().collect());
+        (game, matches)
+    }
+
+    /// Hands the controls to an autopilot heading for the nearest pad, or hovering
+    /// if there is none.
+    fn engage_autopilot(&mut self) {
+        let x = self.player_data::<Position>().current.x;
+        let autopilot = Autopilot::nearest_pad(&self.terrain, x).unwrap_or_else(Autopilot::hover);
+        self.pilot = Some(Box::new(autopilot));
+    }
+
+    #[cfg(feature = "graphics")]
+    fn toggle_autopilot(&mut self) {
+        if self.pilot.take().is_none() {
+            self.engage_autopilot();
+        }
+    }
+
+    fn lander_state(&self) -> LanderState {
+        let tank = self.player_data::<FuelTank>();
+        let thruster = self.player_data::<Thruster>();
+        LanderState {
+            position: self.player_data::<Position>().current,
+            velocity: self.player_data::<Velocity>().0,
+            attitude: self.attitude(),
+            max_acceleration: Vector::from(thruster.force).norm() / tank.mass(),
+            fuel: tank.fuel,
+        }
+    }
+
+    fn fuel(&self) -> D {
+        self.player_data::<FuelTank>().fuel
+    }
+
+    /// Whether the lander is still flying, has landed or crashed.
+    pub fn outcome(&self) -> FlightOutcome {
+        self.outcome
+    }
+
+    /// Points scored by landing so far.
+    pub fn score(&self) -> u16 {
+        self.score
+    }
+
+    // Throttle the engine actually burns with
+    #[cfg(feature = "graphics")]
+    fn throttle(&self) -> D {
+        if self.outcome == FlightOutcome::InFlight && self.fuel() > 0. {
+            self.player_data::<Thruster>().throttle
+        } else {
+            0.
+        }
+    }
+
+    /// Sets the fuel left in the player's tank, as carried over from the last flight.
+    fn refuel(&mut self, fuel: D) {
+        if let Some(tank) = self.world.entity_data_mut::<FuelTank>(self.player) {
+            tank.fuel = fuel.min(tank.capacity);
+            self.recording.fuel = tank.fuel;
+        }
+    }
+
+    const fn map_length() -> usize {
+        150
+    }
+
+    const fn world_size() -> (f32, f32) {
+        (2400., 600.)
+    }
+
+    #[cfg(feature = "graphics")]
+    const fn screen_size() -> (f32, f32) {
+        (800., 600.)
+    }
+
+    const fn pad_count() -> usize {
+        4
+    }
+
+    // Input of the next tick from the replay, the pilot or else the `player`,
+    // none once a replay is over
+    fn next_frame(&mut self, player: impl FnOnce() -> ControlInput) -> Option<Frame> {
+        let state = self.lander_state();
+        match (self.playback.as_mut(), self.pilot.as_mut()) {
+            (Some(frames), _) => frames.pop_front(),
+            (None, Some(pilot)) => Some(Frame {
+                input: pilot.control(&state, &self.terrain, &self.profile, TICK),
+                delta: TICK,
+            }),
+            (None, None) => Some(Frame {
+                input: player(),
+                delta: TICK,
+            }),
+        }
+    }
+
+    // Copy of a component of the player's lander
+    fn player_data<T: Copy + legion::EntityData>(&self) -> T {
+        *self
+            .world
+            .entity_data::<T>(self.player)
+            .expect("Player lander is missing a component")
+    }
+
+    /// Advances the flight by `delta` with `input` on the controls, doing nothing
+    /// once the lander is down.
+    pub fn step(&mut self, input: ControlInput, delta: Duration) {
+        if self.outcome != FlightOutcome::InFlight {
+            return;
+        }
+        let input = if input.abort {
+            self.abort_input()
+        } else {
+            input
+        };
+        self.recording.record(input, delta);
+        self.elapsed += delta;
+        if let Some(orientation) = self.world.entity_data_mut::<Orientation>(self.player) {
+            orientation.turning = input.rotate;
+        }
+        if let Some(thruster) = self.world.entity_data_mut::<Thruster>(self.player) {
+            thruster.throttle = input.throttle;
+        }
+        systems::steering(&self.world, delta);
+        systems::gravity(&self.world, self.profile.gravity(), delta);
+        systems::thrust(&self.world, delta);
+        systems::movement(&self.world, delta);
+        systems::confine(&self.world, &self.terrain);
+        if systems::collision(&self.world, &self.terrain).contains(&self.player) {
+            self.outcome = self.judge_touchdown();
+            if let Some(velocity) = self.world.entity_data_mut::<Velocity>(self.player) {
+                self.touchdown = Some(velocity.0);
+                velocity.0 = Vector::zeros();
+            }
+            if let Some(position) = self.world.entity_data_mut::<Position>(self.player) {
+                position.previous = position.current;
+            }
+            match self.outcome {
+                FlightOutcome::Landed => {
+                    self.score = self.score.saturating_add(self.landing_score())
+                }
+                FlightOutcome::Crashed(_) => self.wreck(),
+                FlightOutcome::InFlight => {}
+            }
+        }
+    }
+
+    // Breaks the lander into fragments flying off with the speed it crashed at
+    fn wreck(&mut self) {
+        let position = self.player_data::<Position>().current;
+        let angle = self.player_data::<Orientation>().angle();
+        let hull = self
+            .world
+            .shared::<Collider>(self.player)
+            .expect("Player lander has no collider")
+            .world_hull(position, angle);
+        let velocity = self.touchdown.unwrap_or_else(Vector::zeros);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        components::spawn_debris(&mut self.world, &hull, velocity, &mut rng);
+    }
+
+    /// Whether the flight is over and any wreck has come to rest.
+    #[cfg(feature = "graphics")]
+    fn finished(&self) -> bool {
+        match self.outcome {
+            FlightOutcome::InFlight => false,
+            FlightOutcome::Landed => true,
+            FlightOutcome::Crashed(_) => systems::settled(&self.world),
+        }
+    }
+
+    fn judge_touchdown(&self) -> FlightOutcome {
+        let position = self.player_data::<Position>().current;
+        let orientation = self.player_data::<Orientation>();
+        let hull = self
+            .world
+            .shared::<Collider>(self.player)
+            .expect("Player lander has no collider")
+            .world_hull(position, orientation.angle());
+        let left = hull.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+        let right = hull.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max);
+        let up = self
+            .terrain
+            .normal_at(position.x)
+            .unwrap_or_else(|| -Vector::y());
+        if self.player_data::<Velocity>().0.norm() > self.profile.max_landing_speed {
+            FlightOutcome::Crashed(CrashReason::TooFast)
+        } else if orientation.tilt(up) > self.profile.max_landing_tilt {
+            FlightOutcome::Crashed(CrashReason::Tilted)
+        } else if !self.terrain.is_flat(left..right) {
+            FlightOutcome::Crashed(CrashReason::UnevenGround)
+        } else {
+            FlightOutcome::Landed
+        }
+    }
+
+    // Base points plus a bonus of up to 100 for a full tank, times the pad multiplier
+    fn landing_score(&self) -> u16 {
+        let multiplier = self
+            .terrain
+            .pad_at(self.player_data::<Position>().current.x)
+            .map_or(1, |pad| pad.multiplier);
+        let tank = self.player_data::<FuelTank>();
+        (LANDING_SCORE + (tank.fuel / tank.capacity * 100.) as u16).saturating_mul(multiplier)
+    }
+
+    // Turns towards upright while burning at full thrust
+    fn abort_input(&self) -> ControlInput {
+        let attitude = self.attitude();
+        let rotate = if attitude.abs() > Orientation::step_angle() / 2. {
+            -attitude.signum()
+        } else {
+            0.
+        };
+        ControlInput {
+            rotate,
+            throttle: 1.,
+            abort: false,
+        }
+    }
+
+    // Radians away from upright, positive when leaning right
+    fn attitude(&self) -> D {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        let angle = self.player_data::<Orientation>().angle();
+        (angle + FRAC_PI_2 + PI).rem_euclid(2. * PI) - PI
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl Moonar {
+    fn plume(&self) -> Option<Plume> {
+        let thruster = self.player_data::<Thruster>();
+        if self.outcome != FlightOutcome::InFlight || thruster.throttle <= 0. || self.fuel() <= 0. {
+            return None;
+        }
+        let rotation = na::Rotation2::new(self.player_data::<Orientation>().angle());
+        let position = self.player_data::<Position>().current;
+        Some(Plume {
+            nozzle: position + rotation * components::nozzle().coords,
+            direction: rotation * -Vector::x(),
+            velocity: self.player_data::<Velocity>().0,
+            throttle: thruster.throttle,
+        })
+    }
+
+    fn telemetry(&self, alpha: D) -> Telemetry {
+        let position = self.player_data::<Position>().interpolate(alpha);
+        let tank = self.player_data::<FuelTank>();
+        Telemetry {
+            level: self.level,
+            seed: self.seed,
+            score: self.score,
+            elapsed: self.elapsed,
+            fuel: tank.fuel / tank.capacity,
+            altitude: self
+                .terrain
+                .surface_at(position.x)
+                .map(|surface| surface - position.y),
+            velocity: self.player_data::<Velocity>().0,
+            attitude: self.attitude(),
+            max_speed: self.profile.max_landing_speed,
+            max_tilt: self.profile.max_landing_tilt,
+        }
+    }
+
+    fn draw_map(&self, ctx: &mut Context) -> GameResult {
+        let points = self.terrain.points();
+        let mut builder = MeshBuilder::new();
+        builder.line(&points, 1., white())?;
+        for pad in self.terrain.pads() {
+            builder.line(&points[pad.start..=pad.end()], 3., white())?;
+        }
+        let mesh = builder.build(ctx)?;
+        for offset in self.camera.copies() {
+            let shift = Vector::new(offset, 0.);
+            mesh.draw(
+                ctx,
+                self.camera
+                    .transform(DrawParam::default().dest(Point::origin() + shift)),
+            )?;
+            for pad in self.terrain.pads() {
+                let label = Text::new(format!("x{}", pad.multiplier));
+                let anchor = points[pad.start] + shift + Vector::new(0., 4.);
+                label.draw(ctx, DrawParam::default().dest(self.camera.project(anchor)))?;
+            }
+        }
+        GameResult::Ok(())
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut Context,
+        bindings: &Bindings,
+        gamepad: &mut Gamepad,
+    ) -> GameResult {
+        while timer::check_update_time(ctx, TICKS_PER_SECOND) {
+            let frame = self
+                .next_frame(|| ControlInput::from_keyboard(ctx, bindings).merge(gamepad.read(ctx)));
+            // Particles keep moving once a replay has run out of frames
+            let (mut plume, mut delta) = (None, TICK);
+            if let Some(frame) = frame {
+                let was_flying = self.outcome == FlightOutcome::InFlight;
+                self.step(frame.input, frame.delta);
+                plume = self.plume();
+                delta = frame.delta;
+                if was_flying && self.outcome != FlightOutcome::InFlight {
+                    // A replay that cannot be written is no reason to end the game
+                    if let Some(path) = &self.record_to {
+                        if let Err(error) = self.recording.save(path) {
+                            eprintln!("cannot save the replay to {}: {}", path.display(), error);
+                        }
+                    }
+                }
+            }
+            let gravity = Vector::from(self.profile.gravity());
+            self.particles.update(plume, &self.terrain, gravity, delta);
+            // Nothing moves anymore once the wreck has settled
+            let tumbling = matches!(self.outcome, FlightOutcome::Crashed(_));
+            if tumbling && !systems::settled(&self.world) {
+                systems::tumble(&self.world, &self.terrain, self.profile.gravity(), TICK);
+                systems::confine(&self.world, &self.terrain);
+            }
+        }
+        GameResult::Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let alpha = timer::remaining_update_time(ctx).as_secs_f32() / TICK.as_secs_f32();
+        let lander = self.player_data::<Position>().interpolate(alpha);
+        let telemetry = self.telemetry(alpha);
+        self.camera
+            .follow(lander, telemetry.altitude, timer::delta(ctx));
+        self.draw_map(ctx)?;
+        self.particles.draw(ctx, &self.camera)?;
+        // A wrecked lander is only drawn as its fragments
+        if let FlightOutcome::Crashed(_) = self.outcome {
+            systems::draw_debris(&self.world, ctx, &self.camera, alpha)?;
+        } else {
+            systems::draw(&self.world, ctx, &self.camera, alpha)?;
+        }
+        telemetry.draw(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Topology;
+
+    // Distance from the lander's center to its base when upright
+    static HALF_HEIGHT: D = 15.;
+    // Upright, with the nose pointing up the screen
+    static UPRIGHT: u8 = Orientation::dir_count() * 3 / 4;
+
+    // Flies one tick with the lander's base a hair above `ground` at `x`,
+    // moving at `velocity` and turned `dir` steps
+    fn touch_down(terrain: Terrain, x: D, ground: D, velocity: Vector, dir: u8) -> FlightOutcome {
+        let mut flight = Moonar::with_seed(0, PhysicsProfile::moon());
+        flight.terrain = terrain;
+        let at = Point::new(x, ground - HALF_HEIGHT - 0.01);
+        *flight
+            .world
+            .entity_data_mut::<Position>(flight.player)
+            .unwrap() = Position::new(at);
+        flight
+            .world
+            .entity_data_mut::<Velocity>(flight.player)
+            .unwrap()
+            .0 = velocity;
+        flight
+            .world
+            .entity_data_mut::<Orientation>(flight.player)
+            .unwrap()
+            .dir = dir;
+        flight.step(ControlInput::default(), TICK);
+        flight.outcome
+    }
+
+    fn flat(x: D, velocity: Vector, dir: u8) -> FlightOutcome {
+        touch_down(Terrain::flat(Topology::Bounded), x, 500., velocity, dir)
+    }
+
+    #[test]
+    fn slow_upright_touchdown_on_level_ground_lands() {
+        assert_eq!(
+            flat(500., Vector::new(0., 5.), UPRIGHT),
+            FlightOutcome::Landed
+        );
+        // One step off upright is within the moon's tilt limit
+        assert_eq!(
+            flat(500., Vector::new(0., 5.), UPRIGHT + 1),
+            FlightOutcome::Landed
+        );
+    }
+
+    #[test]
+    fn touchdown_faster_than_the_limit_crashes() {
+        let too_fast = FlightOutcome::Crashed(CrashReason::TooFast);
+        assert_eq!(flat(500., Vector::new(0., 20.), UPRIGHT), too_fast);
+        // Neither component is over the limit, their combined speed is
+        assert_eq!(flat(500., Vector::new(10., 12.), UPRIGHT), too_fast);
+        // Speed is judged before attitude
+        assert_eq!(flat(500., Vector::new(0., 20.), UPRIGHT + 4), too_fast);
+    }
+
+    #[test]
+    fn touchdown_leaning_too_far_crashes() {
+        assert_eq!(
+            flat(500., Vector::new(0., 5.), UPRIGHT + 2),
+            FlightOutcome::Crashed(CrashReason::Tilted)
+        );
+        assert_eq!(
+            flat(500., Vector::new(0., 5.), UPRIGHT - 2),
+            FlightOutcome::Crashed(CrashReason::Tilted)
+        );
+    }
+
+    #[test]
+    fn touchdown_on_a_bump_crashes() {
+        let mut heights = vec![0.; 21];
+        heights[10] = 2.;
+        let bumpy = Terrain::new(heights, 50., 500., Topology::Bounded);
+        assert_eq!(
+            touch_down(bumpy, 500., 498., Vector::new(0., 5.), UPRIGHT),
+            FlightOutcome::Crashed(CrashReason::UnevenGround)
+        );
+    }
+
+    #[test]
+    fn touchdown_across_the_seam_lands() {
+        for &x in &[2., 998.] {
+            let terrain = Terrain::flat(Topology::Wrapping);
+            assert_eq!(
+                touch_down(terrain, x, 500., Vector::new(0., 5.), UPRIGHT),
+                FlightOutcome::Landed,
+                "{}",
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn touchdown_hanging_over_the_end_of_the_world_crashes() {
+        // Part of the base has no ground below it
+        assert_eq!(
+            flat(2., Vector::new(0., 5.), UPRIGHT),
+            FlightOutcome::Crashed(CrashReason::UnevenGround)
+        );
+        assert_eq!(
+            flat(998., Vector::new(0., 5.), UPRIGHT),
+            FlightOutcome::Crashed(CrashReason::UnevenGround)
+        );
+    }
+}
@@ -1,223 +1,210 @@
-use ggez::{event::EventHandler, graphics::*, timer, Context, GameResult};
-use nalgebra as na;
-use rand;
-use rand::*;
-use std::collections::LinkedList;
-use std::time::Duration;
-
-type D = f32;
-type Vector = na::Vector2<D>;
-type Point = na::Point2<D>;
-
-static MOON_G: Force = Force(0., 8.);
-// Thruster force when lander is pointing to the right
-static THRUSTER: Force = Force(50., 0.);
-static FULL_TURN_MILLIS: u64 = 3000;
-static TURN_TIME: Duration = Duration::from_millis(FULL_TURN_MILLIS / (Lander::dir_count() as u64));
-
-fn white() -> Color {
-    Color::from_rgb(255, 255, 255)
+#[cfg(feature = "graphics")]
+use ggez::{conf, GameError, GameResult};
+use moonar_lander::error::{Error, Result};
+use moonar_lander::neural::Network;
+use moonar_lander::profile::PhysicsProfile;
+use moonar_lander::replay::Replay;
+use moonar_lander::sim::{self, Controller};
+use moonar_lander::D;
+#[cfg(feature = "graphics")]
+use moonar_lander::{audio::Audio, bindings::Bindings, game::Game, Moonar};
+use std::ops::Range;
+use std::path::Path;
+#[cfg(feature = "graphics")]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
 }
 
-fn stroke() -> DrawMode {
-    DrawMode::Stroke(StrokeOptions::default())
+fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Force(pub f32, pub f32);
-
-impl Into<Vector> for Force {
-    fn into(self) -> Vector {
-        Vector::new(self.0, self.1)
-    }
+// Value of an option that has to parse if it is given at all
+fn parsed_arg<T: FromStr>(name: &str) -> Result<Option<T>> {
+    arg_value(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| Error::Config(format!("invalid value {:?} for {}", value, name)))
+        })
+        .transpose()
 }
 
-impl Force {
-    pub fn to_velocity(self, d: Duration) -> Vector {
-        let scale = timer::duration_to_f64(d) as f32;
-        let acceleration: Vector = self.into();
-        acceleration.scale(scale)
-    }
-
-    pub fn per_second(self) -> Vector {
-        self.to_velocity(Duration::from_secs(1))
-    }
+fn parse_seeds(range: &str) -> Option<Range<u64>> {
+    let (start, end) = range.split_once("..")?;
+    Some(start.parse().ok()?..end.parse().ok()?)
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct Lander {
-    dir: u8,
-    turn_cooldown: Duration,
-    coordinates: Point,
-    velocity: Vector,
+fn warn_changed(profile: &str) {
+    eprintln!(
+        "warning: profile {:?} changed since the replay was recorded, it may play back differently",
+        profile
+    );
 }
 
-impl Lander {
-    const fn dir_count() -> u8 {
-        32
-    }
-
-    fn dir(&mut self, d: u8) {
-        self.dir = d % Self::dir_count();
-    }
-
-    fn change_dir(&mut self, d: i8, delta: Duration) {
-        if self.turn_cooldown <= delta {
-            self.turn_cooldown = TURN_TIME;
-            self.dir(((self.dir as i8) + d) as u8)
+// Flies many flights without a window and prints a report on them
+fn simulate() -> Result {
+    let controller = if let Some(path) = arg_value("--replay") {
+        Controller::Replay(Replay::load(Path::new(&path))?)
+    } else if let Some(path) = arg_value("--pilot") {
+        Controller::Network(Network::load(Path::new(&path))?)
+    } else {
+        Controller::Autopilot
+    };
+    let profiles = match (arg_value("--profiles"), &controller) {
+        (Some(names), _) => names
+            .split(',')
+            .map(PhysicsProfile::from_arg)
+            .collect::<Result<Vec<_>>>()?,
+        (None, Controller::Replay(replay)) => PhysicsProfile::preset(&replay.profile)
+            .into_iter()
+            .collect(),
+        (None, _) => vec![PhysicsProfile::default()],
+    };
+    let seeds = match arg_value("--seeds") {
+        Some(range) => parse_seeds(&range)
+            .ok_or_else(|| Error::Config(format!("expected seeds like 0..100, got {:?}", range)))?,
+        None => 0..100,
+    };
+    let level = parsed_arg("--level")?.unwrap_or(1);
+    let min_landing_rate = parsed_arg::<D>("--min-landing-rate")?;
+    if let Controller::Replay(replay) = &controller {
+        let profile = profiles
+            .iter()
+            .find(|profile| profile.name == replay.profile);
+        if let Some(profile) = profile.filter(|profile| !replay.matches(profile)) {
+            warn_changed(&profile.name);
         }
     }
-
-    fn angle(&self) -> D {
-        use std::f32::consts::FRAC_PI_8 as frac;
-
-        frac * (self.dir as f32)
-    }
-}
-
-impl Default for Lander {
-    fn default() -> Self {
-        Lander {
-            dir: 0,
-            turn_cooldown: Duration::from_secs(0),
-            coordinates: Point::new(100., 100.),
-            velocity: Vector::new(0., 0.),
-        }
+    if seeds.is_empty() && !matches!(controller, Controller::Replay(_)) {
+        return Err(Error::Config(format!(
+            "nothing to fly, seeds {:?} are empty",
+            seeds
+        )));
+    }
+    let report = sim::run(&controller, seeds, &profiles, level);
+    if let (0, Controller::Replay(replay)) = (report.flights, &controller) {
+        return Err(Error::Config(format!(
+            "nothing to fly, replay needs profile {:?}, pass it with --profiles",
+            replay.profile
+        )));
+    }
+    if has_flag("--json") {
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|error| Error::Config(format!("cannot write report: {}", error)))?;
+        println!("{}", json);
+    } else {
+        println!("{}", report);
+    }
+    match min_landing_rate {
+        Some(rate) if report.landing_rate < rate => Err(Error::Config(format!(
+            "landing rate {:.3} is below {}",
+            report.landing_rate, rate
+        ))),
+        _ => Ok(()),
     }
 }
 
-impl EventHandler for Lander {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        use ggez::input::keyboard::*;
-        let delta = timer::delta(ctx);
-        let delta_seconds = timer::duration_to_f64(delta) as f32;
-        let mut apply_change = |key| {
-            if is_key_pressed(ctx, key) {
-                let dir = match key {
-                    KeyCode::Left => -1,
-                    KeyCode::Right => 1,
-                    _ => 0,
-                };
-                self.change_dir(dir, timer::delta(ctx))
+#[cfg(feature = "graphics")]
+fn main() -> GameResult {
+    if std::env::args().nth(1).as_deref() == Some("sim") {
+        return Ok(simulate()?);
+    }
+    let record_to = arg_value("--record").map(PathBuf::from);
+    let profile = arg_value("--profile")
+        .map(|arg| PhysicsProfile::from_arg(&arg))
+        .transpose()?;
+    let mut game = match arg_value("--replay") {
+        Some(path) => {
+            let replay = Replay::load(Path::new(&path))?;
+            // Custom profiles have to be passed again, presets are found by name
+            let profile = match profile {
+                Some(profile) => profile,
+                None => PhysicsProfile::preset(&replay.profile).ok_or_else(|| {
+                    GameError::ConfigError(format!(
+                        "replay needs profile {:?}, pass it with --profile",
+                        replay.profile
+                    ))
+                })?,
+            };
+            if profile.name != replay.profile {
+                return Err(GameError::ConfigError(format!(
+                    "replay was recorded with profile {:?} but {:?} was given",
+                    replay.profile, profile.name
+                )));
+            }
+            let name = profile.name.clone();
+            let (flight, matches) = Moonar::from_replay(replay, profile);
+            if !matches {
+                warn_changed(&name);
             }
-        };
-        apply_change(KeyCode::Left);
-        apply_change(KeyCode::Right);
-        self.turn_cooldown = self
-            .turn_cooldown
-            .checked_sub(delta)
-            .unwrap_or(Duration::from_micros(0));
-        let mut delta_v = MOON_G.per_second().scale(delta_seconds);
-        if is_key_pressed(ctx, KeyCode::Space) || is_key_pressed(ctx, KeyCode::Up) {
-            let new_force = THRUSTER.per_second().scale(delta_seconds);
-            let rotation: na::Rotation2<D> = na::Rotation2::new(self.angle());
-            delta_v += rotation * new_force;
+            let mut game = Game::flying(flight);
+            game.record_to(record_to);
+            game
         }
-        self.velocity += delta_v;
-        self.coordinates += self.velocity.scale(delta_seconds);
-        GameResult::Ok(())
-    }
-
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let (width, height) = (15., 30.);
-        let poly = [
-            Point::new(height / 2., 0.),
-            Point::new(-height / 2., width / 2.),
-            Point::new(-height / 2., -width / 2.),
-        ];
-        let params = DrawParam::default()
-            .dest(self.coordinates)
-            .rotation(self.angle());
-        MeshBuilder::new()
-            .polygon(stroke(), &poly, white())?
-            .build(ctx)?
-            .draw(ctx, params)?;
-        GameResult::Ok(())
-    }
-}
-
-#[derive(Clone, Debug, PartialEq)]
-struct Moonar {
-    lander: Lander,
-    heightmap: LinkedList<u32>,
-    score: u16,
-}
-
-impl Default for Moonar {
-    fn default() -> Self {
-        Moonar {
-            lander: Lander::default(),
-            heightmap: Self::generate_heightmap(),
-            score: 0,
+        None => {
+            let seed = parsed_arg("--seed")?.unwrap_or_else(rand::random);
+            let mut game = Game::new(seed, profile.unwrap_or_default());
+            game.record_to(record_to);
+            game
         }
-    }
-}
-
-impl Moonar {
-    const fn max_height() -> u32 {
-        120
-    }
-
-    const fn max_degree() -> u32 {
-        80
-    }
-
-    const fn map_length() -> usize {
-        50
-    }
-
-    fn generate_heightmap() -> LinkedList<u32> {
-        let mut rng = rand::thread_rng();
-        let mut vector = LinkedList::new();
-        let mut last = 0u32;
-        for _ in 0..=Self::map_length() {
-            let next = (last as i32)
-                .checked_add(rng.gen::<i32>() % (Self::max_degree() as i32))
-                .unwrap_or(10);
-            last = (next as u32).max(Self::max_height());
-            vector.push_back(last);
+    };
+    let bindings_path =
+        PathBuf::from(arg_value("--bindings").unwrap_or_else(|| "bindings.toml".to_owned()));
+    let bindings = Bindings::load(&bindings_path).unwrap_or_else(|error| {
+        eprintln!("{}, using the default keys", error);
+        Bindings::default()
+    });
+    game.bindings(bindings, bindings_path);
+    if let Some(dead_zone) = parsed_arg("--dead-zone")? {
+        game.dead_zone(dead_zone);
+    }
+    if has_flag("--autopilot") {
+        game.autopilot();
+    }
+    if let Some(path) = arg_value("--pilot") {
+        game.network(Network::load(Path::new(&path))?);
+    }
+    let builder = || ggez::ContextBuilder::new("moonar", "Paul Martensen");
+    let (mut ctx, mut ev_loop, sound) = match builder().build() {
+        Ok((ctx, ev_loop)) => (ctx, ev_loop, true),
+        // Without an audio device the game runs silently
+        Err(GameError::AudioError(error)) => {
+            eprintln!("sound disabled: {}", error);
+            let modules = conf::ModulesConf {
+                audio: false,
+                ..conf::ModulesConf::default()
+            };
+            let (ctx, ev_loop) = builder().modules(modules).build()?;
+            (ctx, ev_loop, false)
         }
-        vector
-    }
-
-    fn draw_map(&self, ctx: &mut Context) -> GameResult {
-        let (width, w_height) = ggez::graphics::drawable_size(ctx);
-        let segment_width = width / (Self::map_length() as f32);
-        let points: Vec<Point> = self
-            .heightmap
-            .iter()
-            .enumerate()
-            .map(|(index, &height)| Point::new((index as f32) * segment_width, -(height as f32)))
-            .collect();
-        MeshBuilder::new()
-            .line(&points, 1., white())?
-            .build(ctx)?
-            .draw(
-                ctx,
-                DrawParam::default().dest(Point::new(0., w_height * 1.1)),
-            )
-    }
+        Err(error) => return Err(error),
+    };
+    let mut audio = if sound {
+        Audio::new(&mut ctx)
+    } else {
+        Audio::disabled()
+    };
+    if let Some(volume) = parsed_arg("--volume")? {
+        audio.set_volume(volume);
+    }
+    if has_flag("--mute") {
+        audio.toggle_mute();
+    }
+    game.audio(audio);
+    println!("{}", ggez::graphics::renderer_info(&ctx)?);
+    ggez::event::run(&mut ctx, &mut ev_loop, &mut game)
 }
 
-impl EventHandler for Moonar {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        self.lander.update(ctx).expect("Unable to update lander.");
-        GameResult::Ok(())
-    }
-
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        ggez::graphics::clear(ctx, Color::from_rgb(0, 0, 0));
-        self.draw_map(ctx)?;
-        self.lander.draw(ctx)?;
-        ggez::graphics::present(ctx)
+#[cfg(not(feature = "graphics"))]
+fn main() -> Result {
+    match std::env::args().nth(1).as_deref() {
+        Some("sim") => simulate(),
+        _ => Err(Error::Config(
+            "built without the graphics feature, only sim is available".to_owned(),
+        )),
     }
 }
-
-fn main() -> GameResult {
-    let mut game = Moonar::default();
-    let (mut ctx, mut ev_loop) = ggez::ContextBuilder::new("moonar", "Paul Martensen")
-        .build()
-        .unwrap();
-    println!("{}", ggez::graphics::renderer_info(&ctx)?);
-    ggez::event::run(&mut ctx, &mut ev_loop, &mut game)
-}
@@ -0,0 +1,166 @@
+use crate::autopilot::{LanderState, Pilot};
+use crate::env::{self, Observation};
+use crate::error::{Error, Result};
+use crate::terrain::Terrain;
+use crate::{ControlInput, PhysicsProfile, D};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Rotation and throttle
+static OUTPUTS: usize = 2;
+
+/// Small fully connected network flying the lander from what it observes.
+///
+/// On disk a network is a TOML file with the `layers` sizes, from the inputs to
+/// the outputs, and all `weights`, layer after layer, each neuron's bias
+/// followed by the weights of its inputs.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Network {
+    pub layers: Vec<usize>,
+    pub weights: Vec<D>,
+}
+
+impl Network {
+    /// Network with `hidden` layers between the observation and the outputs,
+    /// weights drawn uniformly from -1 to 1.
+    pub fn random(hidden: &[usize], rng: &mut impl Rng) -> Self {
+        let mut layers = vec![Observation::size()];
+        layers.extend_from_slice(hidden);
+        layers.push(OUTPUTS);
+        let weights = (0..Self::weight_count(&layers))
+            .map(|_| rng.gen_range(-1., 1.))
+            .collect();
+        Network { layers, weights }
+    }
+
+    pub fn weight_count(layers: &[usize]) -> usize {
+        layers.windows(2).map(|pair| (pair[0] + 1) * pair[1]).sum()
+    }
+
+    /// Activations of the output layer, each between -1 and 1.
+    pub fn forward(&self, inputs: &[D]) -> Vec<D> {
+        let mut weights = self.weights.iter();
+        let mut activations = inputs.to_vec();
+        for &size in &self.layers[1..] {
+            activations = (0..size)
+                .map(|_| {
+                    let bias = *weights.next().unwrap_or(&0.);
+                    activations
+                        .iter()
+                        .zip(&mut weights)
+                        .fold(bias, |sum, (input, weight)| sum + input * weight)
+                        .tanh()
+                })
+                .collect();
+        }
+        activations
+    }
+
+    pub fn act(&self, observation: &Observation) -> ControlInput {
+        let outputs = self.forward(&inputs(observation));
+        ControlInput {
+            rotate: outputs[0],
+            throttle: (outputs[1] + 1.) / 2.,
+            abort: false,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let invalid = |message: String| Error::Config(format!("invalid network: {}", message));
+        let network: Self = toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|error| invalid(error.to_string()))?;
+        if network.layers.first() != Some(&Observation::size())
+            || network.layers.last() != Some(&OUTPUTS)
+        {
+            return Err(invalid(format!(
+                "expected {} inputs and {} outputs, got layers {:?}",
+                Observation::size(),
+                OUTPUTS,
+                network.layers
+            )));
+        }
+        if network.weights.len() != Self::weight_count(&network.layers) {
+            return Err(invalid(format!(
+                "layers {:?} need {} weights, got {}",
+                network.layers,
+                Self::weight_count(&network.layers),
+                network.weights.len()
+            )));
+        }
+        Ok(network)
+    }
+
+    pub fn save(&self, path: &Path) -> Result {
+        let source = toml::to_string(self)
+            .map_err(|error| Error::Config(format!("cannot save network: {}", error)))?;
+        fs::write(path, source)?;
+        Ok(())
+    }
+}
+
+impl Pilot for Network {
+    fn control(
+        &mut self,
+        state: &LanderState,
+        terrain: &Terrain,
+        profile: &PhysicsProfile,
+        _: Duration,
+    ) -> ControlInput {
+        self.act(&env::observe(state, terrain, profile))
+    }
+}
+
+// The observation vector scaled roughly into -1..1
+fn inputs(observation: &Observation) -> Vec<D> {
+    let scales = [1000., 1000., 50., 50., PI, 1., 1000.];
+    observation
+        .to_vec()
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| value / scales.get(index).copied().unwrap_or(200.))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn random_network_has_a_weight_for_every_connection() {
+        let network = Network::random(&[4, 3], &mut StdRng::seed_from_u64(1));
+        assert_eq!(network.layers, vec![Observation::size(), 4, 3, OUTPUTS]);
+        assert_eq!(
+            network.weights.len(),
+            (Observation::size() + 1) * 4 + 5 * 3 + 4 * OUTPUTS
+        );
+    }
+
+    #[test]
+    fn outputs_stay_in_range() {
+        let network = Network::random(&[6], &mut StdRng::seed_from_u64(2));
+        let outputs = network.forward(&vec![1000.; Observation::size()]);
+        assert_eq!(outputs.len(), OUTPUTS);
+        assert!(outputs.iter().all(|output| (-1. ..=1.).contains(output)));
+    }
+
+    #[test]
+    fn saved_network_loads_the_same() {
+        let network = Network::random(&[5, 3], &mut StdRng::seed_from_u64(3));
+        let path = crate::scratch_path("network.toml");
+        network.save(&path).unwrap();
+        let loaded = Network::load(&path);
+        let mut wrong = network.clone();
+        wrong.weights.pop();
+        wrong.save(&path).unwrap();
+        let truncated = Network::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), network);
+        assert!(truncated.is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::camera::Camera;
+use crate::terrain::Terrain;
+use crate::{Point, Vector, D};
+use ggez::graphics::{Color, DrawParam, Drawable, MeshBuilder};
+use ggez::{Context, GameResult};
+use nalgebra as na;
+use rand::rngs::StdRng;
+use rand::*;
+use std::time::Duration;
+
+// Exhaust particles per second at full throttle
+static EXHAUST_RATE: D = 240.;
+static EXHAUST_SPEED: D = 120.;
+// Widest angle between an exhaust particle and the plume's axis
+static EXHAUST_SPREAD: D = 0.25;
+static EXHAUST_LIFE: D = 0.6;
+// How far the plume reaches to kick up dust, and how many dust particles per
+// second it kicks up at full throttle right above the ground
+static PLUME_LENGTH: D = 90.;
+static DUST_RATE: D = 160.;
+static DUST_SPEED: D = 60.;
+static DUST_LIFE: D = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Exhaust,
+    Dust,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Particle {
+    kind: Kind,
+    position: Point,
+    velocity: Vector,
+    // Seconds left, and seconds to begin with
+    life: D,
+    lifetime: D,
+}
+
+impl Particle {
+    fn color(&self) -> Color {
+        let fade = (self.life / self.lifetime).max(0.);
+        match self.kind {
+            Kind::Exhaust => Color::new(1., 0.6 + 0.4 * fade, 0.3 * fade, fade),
+            Kind::Dust => Color::new(0.6, 0.6, 0.6, 0.8 * fade),
+        }
+    }
+}
+
+/// Engine plume of a thrusting lander.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plume {
+    pub nozzle: Point,
+    // Unit vector the exhaust leaves the nozzle in
+    pub direction: Vector,
+    // Velocity of the lander, passed on to the exhaust
+    pub velocity: Vector,
+    pub throttle: D,
+}
+
+/// Purely visual exhaust and dust, kept apart from the simulation so it never
+/// changes the outcome of a flight.
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    rng: StdRng,
+}
+
+impl ParticleSystem {
+    pub fn new(seed: u64) -> Self {
+        ParticleSystem {
+            particles: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Moves and ages all particles under `gravity`, then emits new ones from `plume`.
+    pub fn update(
+        &mut self,
+        plume: Option<Plume>,
+        terrain: &Terrain,
+        gravity: Vector,
+        delta: Duration,
+    ) {
+        let seconds = delta.as_secs_f32();
+        for particle in &mut self.particles {
+            particle.life -= seconds;
+            particle.velocity += gravity * seconds;
+            particle.position += particle.velocity * seconds;
+            if let Some(surface) = terrain.surface_at(particle.position.x) {
+                if particle.position.y >= surface {
+                    match particle.kind {
+                        // Turned into dust by the plume instead
+                        Kind::Exhaust => particle.life = 0.,
+                        Kind::Dust => {
+                            particle.position.y = surface;
+                            particle.velocity = Vector::zeros();
+                        }
+                    }
+                }
+            }
+        }
+        self.particles.retain(|particle| particle.life > 0.);
+        if let Some(plume) = plume {
+            self.emit_exhaust(&plume, seconds);
+            self.emit_dust(&plume, terrain, seconds);
+        }
+    }
+
+    // Whole number of particles for `rate` over `seconds`, the fraction by chance
+    fn count(&mut self, rate: D, seconds: D) -> usize {
+        (rate * seconds + self.rng.gen::<D>()) as usize
+    }
+
+    fn emit_exhaust(&mut self, plume: &Plume, seconds: D) {
+        for _ in 0..self.count(EXHAUST_RATE * plume.throttle, seconds) {
+            let angle = self.rng.gen_range(-EXHAUST_SPREAD, EXHAUST_SPREAD);
+            let speed = EXHAUST_SPEED * self.rng.gen_range(0.5, 1.);
+            let direction = na::Rotation2::new(angle) * plume.direction;
+            self.particles.push(Particle {
+                kind: Kind::Exhaust,
+                position: plume.nozzle,
+                velocity: plume.velocity + direction * speed,
+                life: EXHAUST_LIFE,
+                lifetime: EXHAUST_LIFE,
+            });
+        }
+    }
+
+    // Kicks up dust where the plume reaches the ground, the more the closer it is
+    fn emit_dust(&mut self, plume: &Plume, terrain: &Terrain, seconds: D) {
+        let samples = 10;
+        let hit = (1..=samples)
+            .map(|sample| {
+                plume.nozzle + plume.direction * PLUME_LENGTH * sample as D / samples as D
+            })
+            .find(|point| {
+                terrain
+                    .surface_at(point.x)
+                    .is_some_and(|surface| point.y >= surface)
+            });
+        let ground = hit.and_then(|hit| {
+            let surface = terrain.surface_at(hit.x)?;
+            Some((Point::new(hit.x, surface), terrain.normal_at(hit.x)?))
+        });
+        let (ground, up) = match ground {
+            Some(found) => found,
+            None => return,
+        };
+        let strength = (1. - (ground - plume.nozzle).norm() / PLUME_LENGTH).max(0.);
+        let along = Vector::new(-up.y, up.x);
+        for _ in 0..self.count(DUST_RATE * plume.throttle * strength, seconds) {
+            let sideways = self.rng.gen_range(-1., 1.);
+            let rise = self.rng.gen_range(0.1, 0.5);
+            let speed = DUST_SPEED * strength * self.rng.gen_range(0.5, 1.);
+            let life = DUST_LIFE * self.rng.gen_range(0.5, 1.);
+            self.particles.push(Particle {
+                kind: Kind::Dust,
+                position: ground + up,
+                velocity: (along * sideways + up * rise) * speed,
+                life,
+                lifetime: life,
+            });
+        }
+    }
+
+    /// Draws all particles as short streaks in a single mesh.
+    pub fn draw(&self, ctx: &mut Context, camera: &Camera) -> GameResult {
+        if self.particles.is_empty() {
+            return GameResult::Ok(());
+        }
+        let mut builder = MeshBuilder::new();
+        for particle in &self.particles {
+            // Longer the faster, but long enough to be seen even at rest
+            let speed = particle.velocity.norm();
+            let tail = if speed < 50. {
+                Vector::y()
+            } else {
+                particle.velocity * (4. / speed).min(0.02)
+            };
+            builder.line(
+                &[particle.position - tail, particle.position],
+                1.5,
+                particle.color(),
+            )?;
+        }
+        let mesh = builder.build(ctx)?;
+        for offset in camera.copies() {
+            let shift = Point::new(offset, 0.);
+            mesh.draw(ctx, camera.transform(DrawParam::default().dest(shift)))?;
+        }
+        GameResult::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Topology;
+
+    // Full thrust straight down from `height` above the ground
+    fn plume(height: D) -> Plume {
+        Plume {
+            nozzle: Point::new(500., 500. - height),
+            direction: Vector::y(),
+            velocity: Vector::zeros(),
+            throttle: 1.,
+        }
+    }
+
+    fn fly(particles: &mut ParticleSystem, plume: Option<Plume>, seconds: u64) {
+        let tick = Duration::from_millis(10);
+        for _ in 0..seconds * 100 {
+            particles.update(
+                plume,
+                &Terrain::flat(Topology::Bounded),
+                Vector::new(0., 8.),
+                tick,
+            );
+        }
+    }
+
+    fn dust(particles: &ParticleSystem) -> usize {
+        particles
+            .particles
+            .iter()
+            .filter(|particle| particle.kind == Kind::Dust)
+            .count()
+    }
+
+    #[test]
+    fn particles_die_once_their_life_runs_out() {
+        let mut particles = ParticleSystem::new(1);
+        fly(&mut particles, Some(plume(PLUME_LENGTH / 2.)), 1);
+        assert!(!particles.particles.is_empty());
+        assert!(dust(&particles) > 0);
+        fly(&mut particles, None, 2);
+        assert!(particles.particles.is_empty());
+    }
+
+    #[test]
+    fn dust_only_rises_within_reach_of_the_plume() {
+        let mut particles = ParticleSystem::new(2);
+        fly(&mut particles, Some(plume(PLUME_LENGTH + 10.)), 1);
+        assert!(!particles.particles.is_empty());
+        assert_eq!(dust(&particles), 0);
+        fly(&mut particles, Some(plume(PLUME_LENGTH - 10.)), 1);
+        assert!(dust(&particles) > 0);
+    }
+
+    #[test]
+    fn same_seed_emits_the_same() {
+        let emit = |seed| {
+            let mut particles = ParticleSystem::new(seed);
+            fly(&mut particles, Some(plume(PLUME_LENGTH / 2.)), 1);
+            particles.particles
+        };
+        assert_eq!(emit(3), emit(3));
+        assert_ne!(emit(3), emit(4));
+    }
+}
@@ -0,0 +1,227 @@
+use crate::error::{Error, Result};
+use crate::{Force, Orientation, D};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Built-in presets, selectable by name
+static PRESETS: [&str; 3] = [
+    include_str!("../profiles/moon.toml"),
+    include_str!("../profiles/mars.toml"),
+    include_str!("../profiles/earth.toml"),
+];
+
+/// How the lander turns.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationMode {
+    // One of `Orientation::dir_count` directions, stepped with a cooldown
+    #[default]
+    Stepped,
+    // Any angle, turned at a constant angular rate
+    Continuous,
+}
+
+/// Physical constants of a flight, read from a TOML file so new worlds need no code changes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PhysicsProfile {
+    pub name: String,
+    // Downward acceleration
+    pub gravity: D,
+    // Thruster force, the acceleration depends on the current mass
+    pub thrust: D,
+    // Time for a full turn, in either rotation mode
+    pub full_turn_millis: u64,
+    #[serde(default)]
+    pub rotation: RotationMode,
+    pub dry_mass: D,
+    pub fuel_capacity: D,
+    // Fuel mass burned per second of thrust
+    pub burn_rate: D,
+    // Touchdown tolerances
+    pub max_landing_speed: D,
+    pub max_landing_tilt: D,
+}
+
+impl Default for PhysicsProfile {
+    fn default() -> Self {
+        Self::moon()
+    }
+}
+
+impl PhysicsProfile {
+    pub fn moon() -> Self {
+        Self::preset("moon").expect("moon preset is missing")
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .map(|source| Self::parse(source).expect("invalid built-in profile"))
+            .find(|profile| profile.name == name)
+    }
+
+    pub fn parse(source: &str) -> Result<Self> {
+        let profile: Self = toml::from_str(source)
+            .map_err(|error| Error::Config(format!("invalid profile: {}", error)))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    // Rejects values the simulation cannot run with
+    fn validate(&self) -> Result {
+        let invalid = |message: String| {
+            Error::Config(format!("invalid profile {:?}: {}", self.name, message))
+        };
+        // Replays store the name on a line of its own
+        if self.name.is_empty()
+            || self.name.trim() != self.name
+            || self.name.contains(char::is_control)
+        {
+            return Err(invalid(
+                "name must be a single line without surrounding spaces".to_owned(),
+            ));
+        }
+        let values = [
+            ("gravity", self.gravity),
+            ("thrust", self.thrust),
+            ("burn_rate", self.burn_rate),
+            ("max_landing_speed", self.max_landing_speed),
+            ("max_landing_tilt", self.max_landing_tilt),
+        ];
+        for (name, value) in &values {
+            if !value.is_finite() || *value < 0. {
+                return Err(invalid(format!(
+                    "{} must be 0 or more, got {}",
+                    name, value
+                )));
+            }
+        }
+        // The mass divides the thrust and the capacity the fuel gauge
+        let positive = [
+            ("dry_mass", self.dry_mass),
+            ("fuel_capacity", self.fuel_capacity),
+        ];
+        for (name, value) in &positive {
+            if !value.is_finite() || *value <= 0. {
+                return Err(invalid(format!("{} must be above 0, got {}", name, value)));
+            }
+        }
+        if self.turn_time() == Duration::from_secs(0) {
+            return Err(invalid(format!(
+                "full_turn_millis must be at least {}, got {}",
+                Orientation::dir_count(),
+                self.full_turn_millis
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Preset of the given name, otherwise the profile file at that path.
+    pub fn from_arg(arg: &str) -> Result<Self> {
+        match Self::preset(arg) {
+            Some(profile) => Ok(profile),
+            None => Self::load(Path::new(arg)),
+        }
+    }
+
+    /// Hash of all values, to tell whether a replay is played back with the
+    /// physics it was recorded with.
+    pub fn fingerprint(&self) -> u64 {
+        let mut bytes = self.name.as_bytes().to_vec();
+        let values = [
+            self.gravity,
+            self.thrust,
+            self.dry_mass,
+            self.fuel_capacity,
+            self.burn_rate,
+            self.max_landing_speed,
+            self.max_landing_tilt,
+        ];
+        for value in &values {
+            bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.full_turn_millis.to_le_bytes());
+        bytes.push(self.rotation as u8);
+        // FNV-1a, which unlike the std hashers stays the same across Rust versions
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+        })
+    }
+
+    pub fn gravity(&self) -> Force {
+        Force(0., self.gravity)
+    }
+
+    // Thruster force when pointing to the right
+    pub fn thruster(&self) -> Force {
+        Force(self.thrust, 0.)
+    }
+
+    // Time per rotation step
+    pub fn turn_time(&self) -> Duration {
+        Duration::from_millis(self.full_turn_millis / Orientation::dir_count() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moon_with(field: &str, value: &str) -> Result<PhysicsProfile> {
+        let source: String = include_str!("../profiles/moon.toml")
+            .lines()
+            .map(|line| {
+                if line.starts_with(&format!("{} ", field)) {
+                    format!("{} = {}\n", field, value)
+                } else {
+                    format!("{}\n", line)
+                }
+            })
+            .collect();
+        PhysicsProfile::parse(&source)
+    }
+
+    #[test]
+    fn presets_parse() {
+        for name in &["moon", "mars", "earth"] {
+            let profile = PhysicsProfile::preset(name).expect("preset is missing");
+            assert_eq!(&profile.name, name);
+        }
+    }
+
+    #[test]
+    fn saved_profile_parses_the_same() {
+        let profile = PhysicsProfile::preset("earth").unwrap();
+        let source = toml::to_string(&profile).unwrap();
+        assert_eq!(PhysicsProfile::parse(&source).unwrap(), profile);
+    }
+
+    #[test]
+    fn impossible_values_are_rejected() {
+        assert!(moon_with("gravity", "8.0").is_ok());
+        assert!(moon_with("gravity", "-8.0").is_err());
+        assert!(moon_with("fuel_capacity", "-1.0").is_err());
+        assert!(moon_with("fuel_capacity", "0.0").is_err());
+        assert!(moon_with("dry_mass", "0.0").is_err());
+        assert!(moon_with("thrust", "nan").is_err());
+        assert!(moon_with("full_turn_millis", "10").is_err());
+        assert!(moon_with("name", "\"\"").is_err());
+    }
+
+    #[test]
+    fn fingerprint_changes_with_any_value() {
+        let moon = PhysicsProfile::moon();
+        assert_eq!(moon.fingerprint(), PhysicsProfile::moon().fingerprint());
+        let heavier = PhysicsProfile {
+            dry_mass: 1.5,
+            ..moon.clone()
+        };
+        assert_ne!(heavier.fingerprint(), moon.fingerprint());
+    }
+}
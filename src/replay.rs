@@ -0,0 +1,304 @@
+use crate::{ControlInput, PhysicsProfile, D};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::Duration;
+
+static HEADER: &str = "moonar-replay";
+static VERSION: u32 = 5;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Input of a single simulation tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub input: ControlInput,
+    pub delta: Duration,
+}
+
+/// Everything needed to play a flight back: the terrain seed and level, the
+/// fuel carried into the flight, the physics profile and one frame per tick.
+///
+/// On disk a replay is a header line with the format version, the seed, level,
+/// fuel, profile name and profile fingerprint and one
+/// `<rotate> <throttle> <delta nanos>` line per frame.
+/// Version 1 files have no level and fuel lines and always start on level 1 with
+/// a full tank, versions before 3 were all flown on the moon, versions before 4
+/// only know full or no throttle and rotation, written as integers, and versions
+/// before 5 have no fingerprint.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub level: u32,
+    pub fuel: D,
+    // Name of the physics profile
+    pub profile: String,
+    // Of the profile's values, to notice profiles changed since the recording
+    pub fingerprint: Option<u64>,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, level: u32, fuel: D, profile: &PhysicsProfile) -> Self {
+        Replay {
+            seed,
+            level,
+            fuel,
+            profile: profile.name.clone(),
+            fingerprint: Some(profile.fingerprint()),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Whether `profile` has the name and, if known, the values this was recorded with.
+    pub fn matches(&self, profile: &PhysicsProfile) -> bool {
+        profile.name == self.profile
+            && self
+                .fingerprint
+                .iter()
+                .all(|&fingerprint| fingerprint == profile.fingerprint())
+    }
+
+    pub fn record(&mut self, input: ControlInput, delta: Duration) {
+        self.frames.push(Frame { input, delta });
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "{} {}", HEADER, VERSION)?;
+        writeln!(file, "seed {}", self.seed)?;
+        writeln!(file, "level {}", self.level)?;
+        writeln!(file, "fuel {}", self.fuel)?;
+        writeln!(file, "profile {}", self.profile)?;
+        match self.fingerprint {
+            Some(fingerprint) => writeln!(file, "fingerprint {}", fingerprint)?,
+            None => writeln!(file, "fingerprint unknown")?,
+        }
+        for frame in &self.frames {
+            writeln!(
+                file,
+                "{} {} {}",
+                frame.input.rotate,
+                frame.input.throttle,
+                frame.delta.as_nanos()
+            )?;
+        }
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut lines = BufReader::new(fs::File::open(path)?).lines();
+        let mut next_line = || {
+            lines
+                .next()
+                .unwrap_or_else(|| Err(invalid("unexpected end of replay".to_owned())))
+        };
+        let header = next_line()?;
+        let version = header
+            .strip_prefix(HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .filter(|&version| version >= 1 && version <= VERSION)
+            .ok_or_else(|| invalid(format!("unsupported replay header {:?}", header)))?;
+        let mut field = |name: &str| {
+            let line = next_line()?;
+            line.strip_prefix(name)
+                .map(|value| value.trim().to_owned())
+                .ok_or_else(|| invalid(format!("expected {} but got {:?}", name, line)))
+        };
+        let parse_error = |name: &str| invalid(format!("invalid {}", name));
+        let seed = field("seed")?.parse().map_err(|_| parse_error("seed"))?;
+        let (level, fuel) = if version >= 2 {
+            (
+                field("level")?.parse().map_err(|_| parse_error("level"))?,
+                field("fuel")?.parse().map_err(|_| parse_error("fuel"))?,
+            )
+        } else {
+            (1, PhysicsProfile::moon().fuel_capacity)
+        };
+        let profile = if version >= 3 {
+            field("profile")?
+        } else {
+            PhysicsProfile::moon().name
+        };
+        let fingerprint = if version >= 5 {
+            match field("fingerprint")?.as_str() {
+                "unknown" => None,
+                value => Some(value.parse().map_err(|_| parse_error("fingerprint"))?),
+            }
+        } else {
+            None
+        };
+        let frames = lines
+            .map(|line| {
+                let line = line?;
+                Self::parse_frame(&line).ok_or_else(|| invalid(format!("invalid frame {:?}", line)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Replay {
+            seed,
+            level,
+            fuel,
+            profile,
+            fingerprint,
+            frames,
+        })
+    }
+
+    fn parse_frame(line: &str) -> Option<Frame> {
+        let mut fields = line.split_whitespace();
+        let rotate = fields.next()?.parse().ok()?;
+        let throttle = fields.next()?.parse().ok()?;
+        let delta = Duration::from_nanos(fields.next()?.parse().ok()?);
+        Some(Frame {
+            input: ControlInput {
+                rotate,
+                throttle,
+                ..ControlInput::default()
+            },
+            delta,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Position;
+    use crate::{scratch_path, FlightOutcome, Moonar};
+
+    fn load(name: &str, contents: &str) -> io::Result<Replay> {
+        let path = scratch_path(&format!("replay-{}", name));
+        fs::write(&path, contents)?;
+        let replay = Replay::load(&path);
+        fs::remove_file(&path)?;
+        replay
+    }
+
+    #[test]
+    fn saved_replay_loads_the_same() {
+        let mut replay = Replay::new(42, 3, 123.5, &PhysicsProfile::preset("mars").unwrap());
+        let input = ControlInput {
+            rotate: -0.25,
+            throttle: 0.75,
+            abort: false,
+        };
+        replay.record(input, Duration::from_nanos(8_333_333));
+        replay.record(ControlInput::default(), Duration::from_millis(16));
+        let path = scratch_path("replay-round-trip");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), replay);
+    }
+
+    #[test]
+    fn unknown_fingerprint_survives_saving() {
+        let replay = Replay {
+            fingerprint: None,
+            ..Replay::new(1, 1, 1., &PhysicsProfile::moon())
+        };
+        let path = scratch_path("replay-unknown-fingerprint");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), replay);
+    }
+
+    #[test]
+    fn version_1_starts_on_level_1_with_a_full_tank_on_the_moon() {
+        let replay = load("v1", "moonar-replay 1\nseed 7\n1 0 8333333\n-1 1 8333333\n").unwrap();
+        assert_eq!(replay.seed, 7);
+        assert_eq!(replay.level, 1);
+        assert_eq!(replay.fuel, PhysicsProfile::moon().fuel_capacity);
+        assert_eq!(replay.profile, "moon");
+        assert_eq!(replay.frames.len(), 2);
+        assert_eq!(replay.frames[1].input.rotate, -1.);
+        assert_eq!(replay.frames[1].input.throttle, 1.);
+    }
+
+    #[test]
+    fn version_2_carries_level_and_fuel() {
+        let replay = load(
+            "v2",
+            "moonar-replay 2\nseed 7\nlevel 4\nfuel 250.5\n0 1 1000\n",
+        )
+        .unwrap();
+        assert_eq!(replay.level, 4);
+        assert_eq!(replay.fuel, 250.5);
+        assert_eq!(replay.profile, "moon");
+        assert_eq!(replay.frames[0].delta, Duration::from_nanos(1000));
+    }
+
+    #[test]
+    fn version_3_names_the_profile() {
+        let source = "moonar-replay 3\nseed 7\nlevel 1\nfuel 10\nprofile earth\n1 1 1000\n";
+        assert_eq!(load("v3", source).unwrap().profile, "earth");
+    }
+
+    #[test]
+    fn version_4_has_partial_controls() {
+        let source = "moonar-replay 4\nseed 7\nlevel 1\nfuel 10\nprofile mars\n0.5 0.25 1000\n";
+        let replay = load("v4", source).unwrap();
+        let input = replay.frames[0].input;
+        assert_eq!((input.rotate, input.throttle), (0.5, 0.25));
+        assert_eq!(replay.fingerprint, None);
+        assert!(replay.matches(&PhysicsProfile::preset("mars").unwrap()));
+    }
+
+    #[test]
+    fn unknown_versions_and_broken_frames_are_rejected() {
+        let newer = format!("moonar-replay {}\nseed 7\n", VERSION + 1);
+        assert!(load("newer", &newer).is_err());
+        assert!(load("frame", "moonar-replay 1\nseed 7\n1 full 1000\n").is_err());
+    }
+
+    #[test]
+    fn changed_profile_does_not_match() {
+        let moon = PhysicsProfile::moon();
+        let replay = Replay::new(1, 1, moon.fuel_capacity, &moon);
+        assert!(replay.matches(&moon));
+        let stronger = PhysicsProfile {
+            thrust: moon.thrust * 2.,
+            ..moon.clone()
+        };
+        assert!(!replay.matches(&stronger));
+        assert!(!replay.matches(&PhysicsProfile::preset("mars").unwrap()));
+    }
+
+    // Flies until the flight is over or, when playing back, the frames run out
+    fn fly(flight: &mut Moonar) {
+        while flight.outcome == FlightOutcome::InFlight {
+            match flight.next_frame(ControlInput::default) {
+                Some(frame) => flight.step(frame.input, frame.delta),
+                None => break,
+            }
+        }
+    }
+
+    #[test]
+    fn playback_repeats_the_flight_exactly() {
+        for name in &["mars", "earth"] {
+            let profile = PhysicsProfile::preset(name).unwrap();
+            let mut flight = Moonar::with_level(11, 2, profile.clone());
+            flight.engage_autopilot();
+            fly(&mut flight);
+            assert_ne!(flight.outcome, FlightOutcome::InFlight);
+
+            let (mut playback, matches) = Moonar::from_replay(flight.recording.clone(), profile);
+            assert!(matches);
+            fly(&mut playback);
+            assert_eq!(playback.outcome, flight.outcome, "{}", name);
+            assert_eq!(playback.elapsed, flight.elapsed, "{}", name);
+            assert_eq!(playback.fuel(), flight.fuel(), "{}", name);
+            assert_eq!(
+                playback.player_data::<Position>(),
+                flight.player_data::<Position>(),
+                "{}",
+                name
+            );
+            assert_eq!(playback.touchdown, flight.touchdown, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::neural::Network;
+use crate::replay::Replay;
+use crate::{ControlInput, CrashReason, FlightOutcome, Moonar, PhysicsProfile, D};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+// Flights still in the air after this long are cut off
+static TIME_LIMIT: Duration = Duration::from_secs(180);
+
+/// What flies the simulated flights.
+pub enum Controller {
+    Autopilot,
+    Network(Network),
+    // Flies its own seed, level and profile once
+    Replay(Replay),
+}
+
+impl Controller {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Controller::Autopilot => "autopilot",
+            Controller::Network(_) => "network",
+            Controller::Replay(_) => "replay",
+        }
+    }
+
+    fn flight(&self, seed: u64, level: u32, profile: &PhysicsProfile) -> Moonar {
+        match self {
+            Controller::Autopilot => {
+                let mut flight = Moonar::with_level(seed, level, profile.clone());
+                flight.engage_autopilot();
+                flight
+            }
+            Controller::Network(network) => {
+                let mut flight = Moonar::with_level(seed, level, profile.clone());
+                flight.pilot = Some(Box::new(network.clone()));
+                flight
+            }
+            Controller::Replay(replay) => Moonar::from_replay(replay.clone(), profile.clone()).0,
+        }
+    }
+}
+
+/// Result of a single simulated flight.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FlightReport {
+    pub seed: u64,
+    pub profile: String,
+    // landed, crashed or timed_out
+    pub outcome: &'static str,
+    pub crash_reason: Option<&'static str>,
+    // Share of a full tank burned
+    pub fuel_used: D,
+    pub touchdown_speed: Option<D>,
+    pub score: u16,
+    pub seconds: D,
+}
+
+/// Spread of a value over all flights it applies to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub count: usize,
+    pub min: D,
+    pub mean: D,
+    pub median: D,
+    pub p90: D,
+    pub max: D,
+}
+
+impl Stats {
+    pub fn of(mut values: Vec<D>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(D::total_cmp);
+        let at = |share: D| values[((values.len() - 1) as D * share).round() as usize];
+        Stats {
+            count: values.len(),
+            min: values[0],
+            mean: values.iter().sum::<D>() / values.len() as D,
+            median: at(0.5),
+            p90: at(0.9),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "min {:.2}  mean {:.2}  median {:.2}  p90 {:.2}  max {:.2}",
+            self.min, self.mean, self.median, self.p90, self.max
+        )
+    }
+}
+
+/// Summary of a batch of simulated flights.
+///
+/// `Display` gives the human readable report, serializing it gives the JSON one.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub controller: &'static str,
+    pub flights: usize,
+    pub landed: usize,
+    pub landing_rate: D,
+    pub timed_out: usize,
+    // Number of crashes per reason
+    pub crashes: BTreeMap<&'static str, usize>,
+    pub fuel_used: Stats,
+    // Of landings and crashes alike
+    pub touchdown_speed: Stats,
+    // Of landings only
+    pub score: Stats,
+    pub runs: Vec<FlightReport>,
+}
+
+impl Report {
+    fn new(controller: &'static str, runs: Vec<FlightReport>) -> Self {
+        let landed = runs.iter().filter(|run| run.outcome == "landed").count();
+        let mut crashes = BTreeMap::new();
+        for reason in runs.iter().filter_map(|run| run.crash_reason) {
+            *crashes.entry(reason).or_insert(0) += 1;
+        }
+        Report {
+            controller,
+            flights: runs.len(),
+            landed,
+            landing_rate: landed as D / runs.len().max(1) as D,
+            timed_out: runs.iter().filter(|run| run.outcome == "timed_out").count(),
+            crashes,
+            fuel_used: Stats::of(runs.iter().map(|run| run.fuel_used).collect()),
+            touchdown_speed: Stats::of(runs.iter().filter_map(|run| run.touchdown_speed).collect()),
+            score: Stats::of(
+                runs.iter()
+                    .filter(|run| run.outcome == "landed")
+                    .map(|run| run.score as D)
+                    .collect(),
+            ),
+            runs,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "controller       {}", self.controller)?;
+        writeln!(f, "flights          {}", self.flights)?;
+        writeln!(
+            f,
+            "landed           {} ({:.1}%)",
+            self.landed,
+            self.landing_rate * 100.
+        )?;
+        for (reason, count) in &self.crashes {
+            writeln!(f, "crashed          {} {}", count, reason.replace('_', " "))?;
+        }
+        if self.timed_out > 0 {
+            writeln!(f, "timed out        {}", self.timed_out)?;
+        }
+        writeln!(f, "fuel used        {}", self.fuel_used)?;
+        writeln!(f, "touchdown speed  {}", self.touchdown_speed)?;
+        write!(f, "landing score    {}", self.score)
+    }
+}
+
+fn crash_name(reason: CrashReason) -> &'static str {
+    match reason {
+        CrashReason::TooFast => "too_fast",
+        CrashReason::Tilted => "tilted",
+        CrashReason::UnevenGround => "uneven_ground",
+    }
+}
+
+fn fly(mut flight: Moonar) -> FlightReport {
+    let fuel = flight.fuel();
+    while flight.outcome == FlightOutcome::InFlight && flight.elapsed < TIME_LIMIT {
+        match flight.next_frame(ControlInput::default) {
+            Some(frame) => flight.step(frame.input, frame.delta),
+            None => break,
+        }
+    }
+    let (outcome, crash_reason) = match flight.outcome {
+        FlightOutcome::InFlight => ("timed_out", None),
+        FlightOutcome::Landed => ("landed", None),
+        FlightOutcome::Crashed(reason) => ("crashed", Some(crash_name(reason))),
+    };
+    FlightReport {
+        seed: flight.seed,
+        profile: flight.profile.name.clone(),
+        outcome,
+        crash_reason,
+        fuel_used: (fuel - flight.fuel()) / flight.profile.fuel_capacity,
+        touchdown_speed: flight.touchdown.map(|velocity| velocity.norm()),
+        score: flight.score,
+        seconds: flight.elapsed.as_secs_f32(),
+    }
+}
+
+/// Flies every seed in `seeds` with every one of `profiles` on `level`,
+/// a replay only its own flight with the profile of the same name.
+pub fn run(
+    controller: &Controller,
+    seeds: Range<u64>,
+    profiles: &[PhysicsProfile],
+    level: u32,
+) -> Report {
+    let runs: Vec<FlightReport> = match controller {
+        Controller::Replay(replay) => profiles
+            .iter()
+            .filter(|profile| profile.name == replay.profile)
+            .take(1)
+            .map(|profile| fly(controller.flight(replay.seed, replay.level, profile)))
+            .collect(),
+        _ => profiles
+            .iter()
+            .flat_map(|profile| {
+                seeds
+                    .clone()
+                    .map(move |seed| fly(controller.flight(seed, level, profile)))
+            })
+            .collect(),
+    };
+    Report::new(controller.name(), runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(outcome: &'static str, crash_reason: Option<&'static str>, score: u16) -> FlightReport {
+        FlightReport {
+            seed: 0,
+            profile: "moon".to_owned(),
+            outcome,
+            crash_reason,
+            fuel_used: 0.5,
+            touchdown_speed: crash_reason.map(|_| 30.).or(Some(4.)),
+            score,
+            seconds: 20.,
+        }
+    }
+
+    #[test]
+    fn stats_of_nothing_are_zero() {
+        assert_eq!(Stats::of(Vec::new()), Stats::default());
+    }
+
+    #[test]
+    fn stats_take_the_nearest_rank() {
+        let stats = Stats::of((1..=10).rev().map(|value| value as D).collect());
+        assert_eq!(stats.count, 10);
+        assert_eq!((stats.min, stats.max), (1., 10.));
+        assert_eq!(stats.mean, 5.5);
+        // Ranks 4.5 and 8.1 of 0 to 9 round to the 6th and 9th value
+        assert_eq!((stats.median, stats.p90), (6., 9.));
+
+        let single = Stats::of(vec![3.]);
+        assert_eq!(
+            (
+                single.min,
+                single.mean,
+                single.median,
+                single.p90,
+                single.max
+            ),
+            (3., 3., 3., 3., 3.)
+        );
+    }
+
+    #[test]
+    fn report_counts_outcomes() {
+        let report = Report::new(
+            "autopilot",
+            vec![
+                run("landed", None, 100),
+                run("landed", None, 300),
+                run("crashed", Some("too_fast"), 0),
+                run("crashed", Some("too_fast"), 0),
+                run("crashed", Some("tilted"), 0),
+                FlightReport {
+                    touchdown_speed: None,
+                    ..run("timed_out", None, 0)
+                },
+            ],
+        );
+        assert_eq!((report.flights, report.landed, report.timed_out), (6, 2, 1));
+        assert_eq!(report.landing_rate, 2. / 6.);
+        assert_eq!(report.crashes.get("too_fast"), Some(&2));
+        assert_eq!(report.crashes.get("tilted"), Some(&1));
+        assert_eq!(report.crashes.get("uneven_ground"), None);
+        // Scores of landings only, touchdown speeds of every flight that touched down
+        assert_eq!((report.score.count, report.score.mean), (2, 200.));
+        assert_eq!(report.touchdown_speed.count, 5);
+        assert_eq!(report.fuel_used.count, 6);
+    }
+
+    #[test]
+    fn empty_report_has_no_landing_rate() {
+        let report = Report::new("autopilot", Vec::new());
+        assert_eq!((report.flights, report.landing_rate), (0, 0.));
+    }
+}
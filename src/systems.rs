@@ -0,0 +1,338 @@
+#[cfg(feature = "graphics")]
+use crate::camera::Camera;
+use crate::components::*;
+use crate::terrain::{Terrain, Topology};
+use crate::{segments_intersect, Force, Point, Vector, D};
+#[cfg(feature = "graphics")]
+use crate::{stroke, white};
+#[cfg(feature = "graphics")]
+use ggez::graphics::{DrawParam, Drawable, MeshBuilder};
+#[cfg(feature = "graphics")]
+use ggez::{Context, GameResult};
+use legion::prelude::*;
+use nalgebra as na;
+use std::time::Duration;
+
+fn seconds(delta: Duration) -> D {
+    delta.as_secs_f32()
+}
+
+pub fn steering(world: &World, delta: Duration) {
+    for orientation in Write::<Orientation>::query().iter(world) {
+        orientation.turn(delta);
+    }
+}
+
+pub fn gravity(world: &World, g: Force, delta: Duration) {
+    let delta_v = g.per_second().scale(seconds(delta));
+    for velocity in Write::<Velocity>::query().iter(world) {
+        velocity.0 += delta_v;
+    }
+}
+
+pub fn thrust(world: &World, delta: Duration) {
+    let delta_seconds = seconds(delta);
+    let query = <(
+        Read<Orientation>,
+        Read<Thruster>,
+        Write<FuelTank>,
+        Write<Velocity>,
+    )>::query();
+    for (orientation, thruster, tank, velocity) in query.iter(world) {
+        if thruster.throttle > 0. && tank.fuel > 0. {
+            let new_force = thruster
+                .force
+                .acceleration(tank.mass())
+                .per_second()
+                .scale(thruster.throttle * delta_seconds);
+            let rotation: na::Rotation2<D> = na::Rotation2::new(orientation.angle());
+            velocity.0 += rotation * new_force;
+            tank.burn(thruster.burn_rate * thruster.throttle * delta_seconds);
+        }
+    }
+}
+
+pub fn movement(world: &World, delta: Duration) {
+    let delta_seconds = seconds(delta);
+    for (position, velocity) in <(Write<Position>, Read<Velocity>)>::query().iter(world) {
+        position.previous = position.current;
+        position.current += velocity.0.scale(delta_seconds);
+    }
+}
+
+/// Wraps positions around a wrapping terrain and stops them at the edges of a bounded one.
+pub fn confine(world: &World, terrain: &Terrain) {
+    let width = terrain.width();
+    for (position, velocity) in <(Write<Position>, Write<Velocity>)>::query().iter(world) {
+        match terrain.topology() {
+            Topology::Wrapping => {
+                let shift = terrain.wrap_x(position.current.x) - position.current.x;
+                position.current.x += shift;
+                position.previous.x += shift;
+            }
+            Topology::Bounded => {
+                if position.current.x < 0. || position.current.x > width {
+                    position.current.x = position.current.x.max(0.).min(width);
+                    velocity.0.x = 0.;
+                }
+            }
+        }
+    }
+}
+
+// Only the game window keeps a wreck moving
+/// Moves, spins and bounces wreck fragments until they come to rest on the ground.
+#[cfg_attr(not(feature = "graphics"), allow(dead_code))]
+pub fn tumble(world: &World, terrain: &Terrain, g: Force, delta: Duration) {
+    // Share of the speed into the ground kept when bouncing, and of the speed
+    // along it kept on every contact
+    let (restitution, friction) = (0.4, 0.8);
+    // Fragments slower than this on the ground, or older, stop moving
+    let (rest_speed, max_age) = (5., 6.);
+    let delta_seconds = seconds(delta);
+    let delta_v = g.per_second().scale(delta_seconds);
+    let query = <(Write<Position>, Write<Velocity>, Write<Fragment>)>::query();
+    for (position, velocity, fragment) in query.iter(world) {
+        position.previous = position.current;
+        if fragment.resting {
+            continue;
+        }
+        fragment.age += delta_seconds;
+        velocity.0 += delta_v;
+        position.current += velocity.0.scale(delta_seconds);
+        fragment.angle += fragment.spin * delta_seconds;
+        let (a, b) = fragment.ends(position.current);
+        let depth = [a, b]
+            .iter()
+            .filter_map(|end| terrain.surface_at(end.x).map(|surface| end.y - surface))
+            .fold(0., D::max);
+        if depth > 0. {
+            position.current.y -= depth;
+            let up = terrain
+                .normal_at(position.current.x)
+                .unwrap_or_else(|| -Vector::y());
+            let into = velocity.0.dot(&up);
+            if into < 0. {
+                velocity.0 -= up * into * (1. + restitution);
+                velocity.0 *= friction;
+                fragment.spin *= -friction;
+            }
+            if velocity.0.norm() < rest_speed {
+                fragment.resting = true;
+            }
+        }
+        if fragment.age > max_age {
+            fragment.resting = true;
+        }
+        if fragment.resting {
+            velocity.0 = Vector::zeros();
+            fragment.spin = 0.;
+        }
+    }
+}
+
+/// Whether all wreck fragments lie still.
+#[cfg_attr(not(feature = "graphics"), allow(dead_code))]
+pub fn settled(world: &World) -> bool {
+    Read::<Fragment>::query()
+        .iter(world)
+        .all(|fragment| fragment.resting)
+}
+
+fn touches_ground(hull: &[Point], terrain: &Terrain) -> bool {
+    let below_ground = hull.iter().any(|p| {
+        terrain
+            .surface_at(p.x)
+            .is_some_and(|surface| p.y >= surface)
+    });
+    let ground = terrain.points();
+    let mut edges = hull.iter().zip(hull.iter().cycle().skip(1));
+    below_ground
+        || edges.any(|(&a, &b)| {
+            ground
+                .windows(2)
+                .any(|segment| segments_intersect((a, b), (segment[0], segment[1])))
+        })
+}
+
+/// Entities whose collider touches the terrain.
+pub fn collision(world: &World, terrain: &Terrain) -> Vec<Entity> {
+    let query = <(Read<Position>, Read<Orientation>, Shared<Collider>)>::query();
+    query
+        .iter_entities(world)
+        .filter(|(_, (position, orientation, collider))| {
+            let hull = collider.world_hull(position.current, orientation.angle());
+            // Near the seam part of the hull lies past the end of a wrapping terrain
+            let shifts: &[D] = match terrain.topology() {
+                Topology::Wrapping => &[0., -1., 1.],
+                Topology::Bounded => &[0.],
+            };
+            shifts.iter().any(|shift| {
+                let offset = Vector::new(shift * terrain.width(), 0.);
+                let shifted: Vec<Point> = hull.iter().map(|p| p + offset).collect();
+                touches_ground(&shifted, terrain)
+            })
+        })
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Draws every renderable entity `alpha` of the way into the current tick.
+#[cfg(feature = "graphics")]
+pub fn draw(world: &World, ctx: &mut Context, camera: &Camera, alpha: D) -> GameResult {
+    let query = <(Read<Position>, Read<Orientation>, Shared<Renderable>)>::query();
+    for (position, orientation, renderable) in query.iter(world) {
+        let params = camera.transform(
+            DrawParam::default()
+                .dest(camera.nearest_copy(position.interpolate(alpha)))
+                .rotation(orientation.angle()),
+        );
+        MeshBuilder::new()
+            .polygon(stroke(), &renderable.outline, renderable.color)?
+            .build(ctx)?
+            .draw(ctx, params)?;
+    }
+    GameResult::Ok(())
+}
+
+/// Draws all wreck fragments `alpha` of the way into the current tick in a single mesh.
+#[cfg(feature = "graphics")]
+pub fn draw_debris(world: &World, ctx: &mut Context, camera: &Camera, alpha: D) -> GameResult {
+    let mut builder = MeshBuilder::new();
+    let mut empty = true;
+    for (position, fragment) in <(Read<Position>, Read<Fragment>)>::query().iter(world) {
+        let (a, b) = fragment.ends(camera.nearest_copy(position.interpolate(alpha)));
+        builder.line(&[a, b], 1., white())?;
+        empty = false;
+    }
+    if !empty {
+        builder
+            .build(ctx)?
+            .draw(ctx, camera.transform(DrawParam::default()))?;
+    }
+    GameResult::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PhysicsProfile;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn wreck(world: &mut World, seed: u64) {
+        let hull = [
+            Point::new(500., 380.),
+            Point::new(485., 410.),
+            Point::new(515., 410.),
+        ];
+        spawn_debris(
+            world,
+            &hull,
+            Vector::new(0., 40.),
+            &mut StdRng::seed_from_u64(seed),
+        );
+    }
+
+    fn states(world: &World) -> Vec<(Point, Vector, Fragment)> {
+        <(Read<Position>, Read<Velocity>, Read<Fragment>)>::query()
+            .iter(world)
+            .map(|(position, velocity, fragment)| (position.current, velocity.0, *fragment))
+            .collect()
+    }
+
+    #[test]
+    fn fragments_fall_bounce_and_settle() {
+        let tick = Duration::from_millis(10);
+        let g = PhysicsProfile::moon().gravity();
+        let terrain = Terrain::flat(Topology::Bounded);
+        let mut world = Universe::new(None).create_world();
+        wreck(&mut world, 1);
+
+        let before = states(&world);
+        tumble(&world, &terrain, g, tick);
+        for ((_, start, _), (_, velocity, _)) in before.iter().zip(states(&world)) {
+            assert!((velocity - start - g.to_velocity(tick)).norm() < 1e-3);
+        }
+
+        let mut bounced = false;
+        let mut previous = states(&world);
+        // Every fragment rests by six seconds at the latest
+        for _ in 0..650 {
+            tumble(&world, &terrain, g, tick);
+            let current = states(&world);
+            bounced |= previous
+                .iter()
+                .zip(&current)
+                .any(|(before, after)| before.1.y > 0. && after.1.y < 0. && !after.2.resting);
+            previous = current;
+        }
+        assert!(bounced);
+        assert!(settled(&world));
+        for (position, velocity, fragment) in states(&world) {
+            assert_eq!(velocity, Vector::zeros());
+            let (a, b) = fragment.ends(position);
+            assert!(a.y <= 500. + 1e-3 && b.y <= 500. + 1e-3, "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn settled_fragments_stay_put() {
+        let terrain = Terrain::flat(Topology::Bounded);
+        let mut world = Universe::new(None).create_world();
+        wreck(&mut world, 2);
+        let g = PhysicsProfile::moon().gravity();
+        while !settled(&world) {
+            tumble(&world, &terrain, g, Duration::from_millis(10));
+        }
+        let resting = states(&world);
+        tumble(&world, &terrain, g, Duration::from_secs(1));
+        assert_eq!(states(&world), resting);
+    }
+
+    // Upright lander whose base is `gap` above the ground at y = 500
+    fn lander(world: &mut World, x: D, gap: D) -> Entity {
+        let entity = spawn_lander(
+            world,
+            Point::new(x, 500. - 15. - gap),
+            &PhysicsProfile::moon(),
+        );
+        world.entity_data_mut::<Orientation>(entity).unwrap().dir =
+            Orientation::dir_count() * 3 / 4;
+        entity
+    }
+
+    #[test]
+    fn hull_collides_only_once_it_reaches_the_ground() {
+        let terrain = Terrain::flat(Topology::Bounded);
+        let mut world = Universe::new(None).create_world();
+        let above = lander(&mut world, 300., 1.);
+        let touching = lander(&mut world, 600., -0.5);
+        let found = collision(&world, &terrain);
+        assert!(!found.contains(&above));
+        assert!(found.contains(&touching));
+    }
+
+    #[test]
+    fn hull_collides_across_the_seam() {
+        let terrain = Terrain::flat(Topology::Wrapping);
+        let mut world = Universe::new(None).create_world();
+        let left = lander(&mut world, 2., -0.5);
+        let right = lander(&mut world, 998., -0.5);
+        let above = lander(&mut world, 998., 1.);
+        let found = collision(&world, &terrain);
+        assert!(found.contains(&left) && found.contains(&right));
+        assert!(!found.contains(&above));
+    }
+
+    #[test]
+    fn hull_past_a_bounded_edge_collides_with_the_ground_it_overhangs() {
+        let terrain = Terrain::flat(Topology::Bounded);
+        let mut world = Universe::new(None).create_world();
+        let hanging = lander(&mut world, 2., -0.5);
+        let above = lander(&mut world, 998., 1.);
+        let found = collision(&world, &terrain);
+        assert!(found.contains(&hanging));
+        assert!(!found.contains(&above));
+    }
+}
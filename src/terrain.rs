@@ -0,0 +1,523 @@
+use crate::{Point, Vector, D};
+use rand::rngs::StdRng;
+use rand::*;
+use std::fmt::Debug;
+use std::ops::Range;
+
+/// Flat stretch of terrain spanning `width` segments from heightmap index `start`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LandingPad {
+    pub start: usize,
+    pub width: usize,
+    pub multiplier: u16,
+}
+
+impl LandingPad {
+    const fn min_width() -> usize {
+        2
+    }
+
+    const fn max_width() -> usize {
+        5
+    }
+
+    // Narrow pads are harder to hit and pay more, as in the arcade
+    fn multiplier_for(width: usize) -> u16 {
+        match width {
+            0..=2 => 5,
+            3 => 3,
+            4 => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.start + self.width
+    }
+}
+
+/// What happens at the left and right end of the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    // The world ends at the edges of the terrain
+    Bounded,
+    // Leaving on one side enters on the other
+    Wrapping,
+}
+
+/// Ground polyline in world units, shared by physics and rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Terrain {
+    // Ground altitude at every segment boundary
+    heights: Vec<D>,
+    segment_width: D,
+    // World y coordinate of altitude zero
+    base: D,
+    topology: Topology,
+    pads: Vec<LandingPad>,
+}
+
+impl Terrain {
+    pub fn new(heights: Vec<D>, segment_width: D, base: D, topology: Topology) -> Self {
+        let mut terrain = Terrain {
+            heights,
+            segment_width,
+            base,
+            topology,
+            pads: Vec::new(),
+        };
+        if topology == Topology::Wrapping {
+            terrain.close_seam();
+        }
+        terrain
+    }
+
+    // Bends the last few points towards the first one so both ends meet
+    fn close_seam(&mut self) {
+        let blend = self.heights.len().min(8);
+        let (first, last) = (self.heights[0], self.heights.len() - 1);
+        for step in 1..blend {
+            let t = step as D / (blend - 1) as D;
+            let index = last + 1 - blend + step;
+            self.heights[index] += (first - self.heights[index]) * t;
+        }
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Maps `x` into the terrain for wrapping worlds, identity otherwise.
+    pub fn wrap_x(&self, x: D) -> D {
+        match self.topology {
+            Topology::Wrapping if self.width() > 0. => x.rem_euclid(self.width()),
+            _ => x,
+        }
+    }
+
+    // Point index past the seam maps back to the start of a wrapping terrain
+    fn wrap_index(&self, index: usize) -> usize {
+        if index > self.segments() {
+            index - self.segments()
+        } else {
+            index
+        }
+    }
+
+    pub fn segments(&self) -> usize {
+        self.heights.len().saturating_sub(1)
+    }
+
+    pub fn width(&self) -> D {
+        self.segments() as D * self.segment_width
+    }
+
+    pub fn pads(&self) -> &[LandingPad] {
+        &self.pads
+    }
+
+    pub fn point(&self, index: usize) -> Point {
+        Point::new(
+            index as D * self.segment_width,
+            self.base - self.heights[index],
+        )
+    }
+
+    pub fn points(&self) -> Vec<Point> {
+        (0..self.heights.len())
+            .map(|index| self.point(index))
+            .collect()
+    }
+
+    /// Index of the segment below `x`, `None` outside the terrain.
+    pub fn segment_at(&self, x: D) -> Option<usize> {
+        let x = self.wrap_x(x);
+        if self.segments() == 0 || x < 0. || x > self.width() {
+            return None;
+        }
+        Some(((x / self.segment_width) as usize).min(self.segments() - 1))
+    }
+
+    /// Ground altitude at `x`.
+    pub fn height_at(&self, x: D) -> Option<D> {
+        let x = self.wrap_x(x);
+        self.segment_at(x).map(|index| {
+            let t = x / self.segment_width - index as D;
+            let (left, right) = (self.heights[index], self.heights[index + 1]);
+            left + t * (right - left)
+        })
+    }
+
+    /// World y coordinate of the ground at `x`.
+    pub fn surface_at(&self, x: D) -> Option<D> {
+        self.height_at(x).map(|height| self.base - height)
+    }
+
+    /// Unit vector perpendicular to the ground at `x`, pointing into the sky.
+    pub fn normal_at(&self, x: D) -> Option<Vector> {
+        self.segment_at(x).map(|index| {
+            let rise = self.heights[index + 1] - self.heights[index];
+            Vector::new(rise, -self.segment_width).normalize()
+        })
+    }
+
+    /// Whether the ground is level across all of `range`.
+    pub fn is_flat(&self, range: Range<D>) -> bool {
+        match (self.segment_at(range.start), self.segment_at(range.end)) {
+            (Some(first), Some(last)) => {
+                // A range ending right on a point stops short of the segment after it
+                let past = self.wrap_x(range.end) / self.segment_width - last as D;
+                let last = if last != first && past < 1e-3 {
+                    (last + self.segments() - 1) % self.segments()
+                } else {
+                    last
+                };
+                // A range over the seam of a wrapping terrain ends in a lower segment
+                let count = if last >= first {
+                    last - first
+                } else {
+                    last + self.segments() - first
+                };
+                let reference = self.heights[first];
+                (first..=first + count + 1).all(|index| {
+                    (self.heights[self.wrap_index(index)] - reference).abs() <= f32::EPSILON
+                })
+            }
+            _ => false,
+        }
+    }
+
+    pub fn pad_bounds(&self, pad: &LandingPad) -> Range<D> {
+        pad.start as D * self.segment_width..pad.end() as D * self.segment_width
+    }
+
+    pub fn pad_at(&self, x: D) -> Option<&LandingPad> {
+        let x = self.wrap_x(x);
+        self.pads.iter().find(|pad| {
+            let bounds = self.pad_bounds(pad);
+            x >= bounds.start && x <= bounds.end
+        })
+    }
+
+    /// Flattens up to `count` non-overlapping pads into the ground.
+    pub fn carve_pads(&mut self, count: usize, rng: &mut impl Rng) {
+        let segments = self.segments();
+        let count = count.min(segments / (LandingPad::max_width() + 1));
+        if count == 0 {
+            return;
+        }
+        let slot = segments / count;
+        self.pads = (0..count)
+            .map(|index| {
+                let width = rng.gen_range(LandingPad::min_width(), LandingPad::max_width() + 1);
+                LandingPad {
+                    start: index * slot + rng.gen_range(0, slot - width),
+                    width,
+                    multiplier: LandingPad::multiplier_for(width),
+                }
+            })
+            .collect();
+        for pad in &self.pads {
+            let height = self.heights[pad.start];
+            for index in pad.start..=pad.end() {
+                self.heights[index] = height;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Terrain {
+    /// Level ground at y = 500 from x = 0 to 1000.
+    pub(crate) fn flat(topology: Topology) -> Self {
+        Terrain::new(vec![0.; 21], 50., 500., topology)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainParams {
+    // Number of segments, the heightmap holds one more point than this
+    pub length: usize,
+    // Lowest height any point may take
+    pub floor: D,
+    // Spread between the lowest and the highest point
+    pub amplitude: D,
+    // 0 gives rolling hills, 1 gives jagged peaks
+    pub roughness: f32,
+    pub topology: Topology,
+}
+
+impl Default for TerrainParams {
+    fn default() -> Self {
+        TerrainParams {
+            length: 150,
+            floor: 120.,
+            amplitude: 250.,
+            roughness: 0.5,
+            topology: Topology::Wrapping,
+        }
+    }
+}
+
+impl TerrainParams {
+    fn points(&self) -> usize {
+        self.length + 1
+    }
+
+    // Maps raw samples of any range onto `floor..=floor + amplitude`
+    fn normalize(&self, samples: &[D]) -> Vec<D> {
+        let min = samples.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = samples.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let range = (max - min).max(f32::EPSILON);
+        samples
+            .iter()
+            .map(|sample| self.floor + (sample - min) / range * self.amplitude)
+            .collect()
+    }
+}
+
+pub trait TerrainGenerator: Debug {
+    fn generate(&self, params: &TerrainParams, rng: &mut StdRng) -> Vec<D>;
+}
+
+/// Random walk where each point differs from the last by at most `roughness * amplitude`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RandomWalk;
+
+impl TerrainGenerator for RandomWalk {
+    fn generate(&self, params: &TerrainParams, rng: &mut StdRng) -> Vec<D> {
+        let max_step = (params.amplitude * params.roughness).max(1.);
+        let (floor, ceiling) = (params.floor, params.floor + params.amplitude);
+        let mut last = rng.gen_range(floor, ceiling);
+        (0..params.points())
+            .map(|_| {
+                last = (last + rng.gen_range(-max_step, max_step))
+                    .max(floor)
+                    .min(ceiling);
+                last
+            })
+            .collect()
+    }
+}
+
+/// Recursive midpoint displacement, the spread shrinks by `roughness` on every level.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MidpointDisplacement;
+
+impl MidpointDisplacement {
+    fn displace(
+        samples: &mut [f32],
+        low: usize,
+        high: usize,
+        spread: f32,
+        roughness: f32,
+        rng: &mut StdRng,
+    ) {
+        if high - low < 2 {
+            return;
+        }
+        let mid = (low + high) / 2;
+        samples[mid] = (samples[low] + samples[high]) / 2. + rng.gen_range(-spread, spread);
+        let spread = (spread * roughness).max(f32::EPSILON);
+        Self::displace(samples, low, mid, spread, roughness, rng);
+        Self::displace(samples, mid, high, spread, roughness, rng);
+    }
+}
+
+impl TerrainGenerator for MidpointDisplacement {
+    fn generate(&self, params: &TerrainParams, rng: &mut StdRng) -> Vec<D> {
+        let mut samples = vec![0.; params.points()];
+        let last = samples.len() - 1;
+        samples[0] = rng.gen_range(-1., 1.);
+        samples[last] = rng.gen_range(-1., 1.);
+        Self::displace(&mut samples, 0, last, 1., params.roughness, rng);
+        params.normalize(&samples)
+    }
+}
+
+/// Fractal 1D Perlin noise, `roughness` is the persistence between octaves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerlinNoise {
+    pub octaves: u32,
+    // Lattice points per segment for the first octave
+    pub frequency: f32,
+}
+
+impl Default for PerlinNoise {
+    fn default() -> Self {
+        PerlinNoise {
+            octaves: 4,
+            frequency: 0.1,
+        }
+    }
+}
+
+impl PerlinNoise {
+    fn fade(t: f32) -> f32 {
+        t * t * t * (t * (t * 6. - 15.) + 10.)
+    }
+
+    fn noise(gradients: &[f32], x: f32) -> f32 {
+        let cell = x.floor() as usize;
+        let t = x - x.floor();
+        let left = gradients[cell % gradients.len()] * t;
+        let right = gradients[(cell + 1) % gradients.len()] * (t - 1.);
+        left + Self::fade(t) * (right - left)
+    }
+}
+
+impl TerrainGenerator for PerlinNoise {
+    fn generate(&self, params: &TerrainParams, rng: &mut StdRng) -> Vec<D> {
+        let lattice = ((params.points() as f32 * self.frequency) as usize + 2) << self.octaves;
+        let gradients: Vec<f32> = (0..lattice).map(|_| rng.gen_range(-1., 1.)).collect();
+        let samples: Vec<f32> = (0..params.points())
+            .map(|index| {
+                let (mut frequency, mut weight, mut sum) = (self.frequency, 1., 0.);
+                for _ in 0..self.octaves {
+                    sum += weight * Self::noise(&gradients, index as f32 * frequency);
+                    frequency *= 2.;
+                    weight *= params.roughness;
+                }
+                sum
+            })
+            .collect();
+        params.normalize(&samples)
+    }
+}
+
+/// Generator and parameters for a level, cycling through the generators and
+/// getting rougher as the player progresses. Every third level is a bounded
+/// world instead of a wrapping one.
+pub fn for_level(level: u32) -> (Box<dyn TerrainGenerator>, TerrainParams) {
+    let (generator, topology): (Box<dyn TerrainGenerator>, _) = match level % 3 {
+        0 => (Box::new(RandomWalk), Topology::Bounded),
+        1 => (Box::new(MidpointDisplacement), Topology::Wrapping),
+        _ => (Box::new(PerlinNoise::default()), Topology::Wrapping),
+    };
+    let params = TerrainParams {
+        roughness: (0.4 + 0.05 * level as f32).min(0.8),
+        topology,
+        ..TerrainParams::default()
+    };
+    (generator, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generators() -> Vec<Box<dyn TerrainGenerator>> {
+        vec![
+            Box::new(RandomWalk),
+            Box::new(MidpointDisplacement),
+            Box::new(PerlinNoise::default()),
+        ]
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_heights() {
+        let params = TerrainParams::default();
+        for generator in generators() {
+            let first = generator.generate(&params, &mut StdRng::seed_from_u64(5));
+            let second = generator.generate(&params, &mut StdRng::seed_from_u64(5));
+            let other = generator.generate(&params, &mut StdRng::seed_from_u64(6));
+            assert_eq!(first, second, "{:?}", generator);
+            assert_ne!(first, other, "{:?}", generator);
+        }
+    }
+
+    #[test]
+    fn heights_stay_above_the_floor_within_the_amplitude() {
+        for level in 1..=6 {
+            let (generator, params) = for_level(level);
+            let heights = generator.generate(&params, &mut StdRng::seed_from_u64(level as u64));
+            assert_eq!(heights.len(), params.length + 1);
+            let range = params.floor - 1e-3..=params.floor + params.amplitude + 1e-3;
+            assert!(
+                heights.iter().all(|height| range.contains(height)),
+                "{:?}",
+                generator
+            );
+        }
+    }
+
+    fn world(heights: Vec<D>, topology: Topology) -> Terrain {
+        Terrain::new(heights, 10., 600., topology)
+    }
+
+    #[test]
+    fn wrapping_terrain_meets_itself_at_the_seam() {
+        let terrain = world(
+            vec![100., 150., 120., 180., 90., 160., 130., 170., 110., 140.],
+            Topology::Wrapping,
+        );
+        assert_eq!(terrain.heights[0], terrain.heights[terrain.segments()]);
+        for x in &[0., 15., 42.5, 89.] {
+            assert_eq!(
+                terrain.surface_at(x + terrain.width()),
+                terrain.surface_at(*x)
+            );
+            assert_eq!(
+                terrain.surface_at(x - terrain.width()),
+                terrain.surface_at(*x)
+            );
+        }
+    }
+
+    #[test]
+    fn bounded_terrain_ends_at_its_edges() {
+        let terrain = world(vec![100.; 10], Topology::Bounded);
+        assert_eq!(terrain.wrap_x(-5.), -5.);
+        assert_eq!(terrain.surface_at(-5.), None);
+        assert_eq!(terrain.surface_at(terrain.width() + 5.), None);
+        assert_eq!(terrain.surface_at(45.), Some(500.));
+    }
+
+    #[test]
+    fn flatness_is_judged_across_the_seam() {
+        let mut heights = vec![50.; 10];
+        let flat = world(heights.clone(), Topology::Wrapping);
+        assert!(flat.is_flat(85.0..95.0));
+        heights[1] = 70.;
+        let bumpy = world(heights, Topology::Wrapping);
+        assert!(!bumpy.is_flat(85.0..95.0));
+    }
+
+    #[test]
+    fn pads_are_flat_apart_and_pay_by_width() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let heights = RandomWalk.generate(&TerrainParams::default(), &mut rng);
+            let mut terrain = world(heights, Topology::Bounded);
+            terrain.carve_pads(3, &mut rng);
+            assert_eq!(terrain.pads().len(), 3);
+            for pad in terrain.pads() {
+                assert!(terrain.is_flat(terrain.pad_bounds(pad)), "{:?}", pad);
+                assert!(pad.end() <= terrain.segments());
+                let multiplier = match pad.width {
+                    2 => 5,
+                    3 => 3,
+                    4 => 2,
+                    5 => 1,
+                    width => panic!("pad {} segments wide", width),
+                };
+                assert_eq!(pad.multiplier, multiplier);
+            }
+            for pair in terrain.pads().windows(2) {
+                assert!(pair[0].end() < pair[1].start, "{:?}", pair);
+            }
+        }
+    }
+
+    #[test]
+    fn pad_count_is_clamped_on_short_terrain() {
+        let slot = LandingPad::max_width() + 1;
+        for segments in 0..3 * slot {
+            for seed in 0..10 {
+                let mut terrain = world(vec![100.; segments + 1], Topology::Bounded);
+                terrain.carve_pads(3, &mut StdRng::seed_from_u64(seed));
+                assert_eq!(terrain.pads().len(), (segments / slot).min(3));
+            }
+        }
+    }
+}